@@ -0,0 +1,1075 @@
+use crate::binary::{Arch, Binary, Function, Mode, Reloc};
+use crate::decoder::{Decoder, Inst};
+use capstone::arch::{self, ArchOperand};
+use capstone::Capstone;
+use capstone::Insn;
+use capstone::InsnGroupId;
+use capstone::InsnGroupType;
+use capstone::{RegId, RegIdInt};
+
+const CALL_INST: u8 = InsnGroupType::CS_GRP_CALL as u8;
+const JUMP_INST: u8 = InsnGroupType::CS_GRP_JUMP as u8;
+const RET_INST: u8 = InsnGroupType::CS_GRP_RET as u8;
+const BRANCH_RELATIVE: u8 = InsnGroupType::CS_GRP_BRANCH_RELATIVE as u8;
+
+#[derive(Clone, Copy)]
+pub enum BranchInst {
+    Call(u64),
+    Jump(u64),
+    // Through pointer stored at the address, like a GOT slot
+    CallVia(u64),
+    JumpVia(u64),
+    Return,
+}
+
+fn imm(op: &ArchOperand) -> Option<u64> {
+    match op {
+        ArchOperand::X86Operand(op) => match op.op_type {
+            arch::x86::X86OperandType::Imm(x) => Some(x as u64),
+            _ => None,
+        },
+        ArchOperand::ArmOperand(op) => match op.op_type {
+            arch::arm::ArmOperandType::Imm(x) => Some(x as u64),
+            _ => None,
+        },
+        ArchOperand::Arm64Operand(op) => match op.op_type {
+            arch::arm64::Arm64OperandType::Imm(x) => Some(x as u64),
+            _ => None,
+        },
+        ArchOperand::MipsOperand(arch::mips::MipsOperand::Imm(x)) => Some(*x as u64),
+        _ => None,
+    }
+}
+
+fn sign_extend(x: u32, bits: u32) -> i64 {
+    ((x << (32 - bits)) as i32 >> (32 - bits)) as i64
+}
+
+// Targets of RISC-V and other fixed encodings below are taken from the
+// encoding, since disassembler prints some of them as offsets. Linking
+// register other than zero means call
+fn riscv_branch(mode: Mode, addr: u64, bytes: &[u8]) -> Option<BranchInst> {
+    let target = |off: i64| Some(addr.wrapping_add(off as u64));
+
+    if bytes.len() == 2 {
+        let h = u16::from_le_bytes([bytes[0], bytes[1]]) as u32;
+        let rs1 = (h >> 7) & 31;
+        let rs2 = (h >> 2) & 31;
+
+        let j_imm = || {
+            let imm = ((h >> 12) & 1) << 11
+                | ((h >> 11) & 1) << 4
+                | ((h >> 9) & 3) << 8
+                | ((h >> 8) & 1) << 10
+                | ((h >> 7) & 1) << 6
+                | ((h >> 6) & 1) << 7
+                | ((h >> 3) & 7) << 1
+                | ((h >> 2) & 1) << 5;
+
+            sign_extend(imm, 12)
+        };
+
+        return match (h & 3, h >> 13) {
+            // c.j
+            (1, 5) => target(j_imm()).map(BranchInst::Jump),
+            // c.jal, which is c.addiw on RV64
+            (1, 1) if !mode.bits64 => target(j_imm()).map(BranchInst::Call),
+            // c.beqz, c.bnez
+            (1, 6) | (1, 7) => {
+                let imm = ((h >> 12) & 1) << 8
+                    | ((h >> 10) & 3) << 3
+                    | ((h >> 5) & 3) << 6
+                    | ((h >> 3) & 3) << 1
+                    | ((h >> 2) & 1) << 5;
+
+                target(sign_extend(imm, 9)).map(BranchInst::Jump)
+            }
+            // c.jr ra
+            (2, 4) if (h >> 12) & 1 == 0 && rs2 == 0 && rs1 == 1 => Some(BranchInst::Return),
+            _ => None,
+        };
+    }
+
+    let w = u32::from_le_bytes(bytes.get(0..4)?.try_into().ok()?);
+    let rd = (w >> 7) & 31;
+    let rs1 = (w >> 15) & 31;
+
+    match w & 0x7f {
+        // jal
+        0x6f => {
+            let imm = (w >> 31) << 20
+                | ((w >> 21) & 0x3ff) << 1
+                | ((w >> 20) & 1) << 11
+                | ((w >> 12) & 0xff) << 12;
+            let off = sign_extend(imm, 21);
+
+            if rd == 0 {
+                target(off).map(BranchInst::Jump)
+            } else {
+                target(off).map(BranchInst::Call)
+            }
+        }
+        // beq, bne, blt, bge, bltu, bgeu
+        0x63 => {
+            let imm = (w >> 31) << 12
+                | ((w >> 25) & 0x3f) << 5
+                | ((w >> 8) & 0xf) << 1
+                | ((w >> 7) & 1) << 11;
+
+            target(sign_extend(imm, 13)).map(BranchInst::Jump)
+        }
+        // jalr zero, 0(ra)
+        0x67 if rd == 0 && rs1 == 1 && w >> 20 == 0 => Some(BranchInst::Return),
+        _ => None,
+    }
+}
+
+// Far calls and tail calls are auipc with the upper part of the offset
+// followed by jalr with the lower one
+fn riscv_pair(prev: &Inst, inst: &Inst) -> Option<BranchInst> {
+    let auipc = u32::from_le_bytes(prev.bytes.get(0..4)?.try_into().ok()?);
+    let jalr = u32::from_le_bytes(inst.bytes.get(0..4)?.try_into().ok()?);
+
+    if auipc & 0x7f != 0x17 || jalr & 0x7f != 0x67 || jalr >> 15 & 31 != auipc >> 7 & 31 {
+        return None;
+    }
+
+    let upper = (auipc & 0xfffff000) as i32 as i64;
+    let lower = (jalr as i32 >> 20) as i64;
+    let target = prev.addr.wrapping_add((upper + lower) as u64);
+
+    if jalr >> 7 & 31 == 0 {
+        Some(BranchInst::Jump(target))
+    } else {
+        Some(BranchInst::Call(target))
+    }
+}
+
+// MIPS groups are not reliable: jal is not a call and bal is not a jump, so
+// instructions linking return address are told by mnemonic
+fn mips_transfer(cs: &Capstone, inst: &Insn) -> bool {
+    let detail = match cs.insn_detail(inst) {
+        Ok(d) => d,
+        Err(_) => return false,
+    };
+
+    matches!(
+        inst.mnemonic(),
+        Some("j") | Some("jal") | Some("jalx") | Some("jals")
+    ) || detail.groups().into_iter().any(|g| {
+        matches!(
+            g,
+            InsnGroupId(CALL_INST)
+                | InsnGroupId(JUMP_INST)
+                | InsnGroupId(RET_INST)
+                | InsnGroupId(BRANCH_RELATIVE)
+        )
+    })
+}
+
+fn mips_branch(cs: &Capstone, inst: &Insn) -> Option<BranchInst> {
+    let mnemonic = inst.mnemonic()?;
+
+    if matches!(mnemonic, "jr" | "jrc" | "jr.hb") && inst.op_str() == Some("$ra") {
+        return Some(BranchInst::Return);
+    }
+
+    if !mips_transfer(cs, inst) {
+        return None;
+    }
+
+    let detail = cs.insn_detail(inst).ok()?;
+    let mut target = detail.arch_detail().operands().iter().rev().find_map(imm)?;
+
+    // j and jal replace low 28 bits of address of the delay slot
+    if matches!(mnemonic, "j" | "jal" | "jalx") {
+        target = (inst.address() + 4) & !0x0fff_ffff | target & 0x0fff_ffff;
+    }
+
+    if mnemonic.contains("al") {
+        Some(BranchInst::Call(target))
+    } else {
+        Some(BranchInst::Jump(target))
+    }
+}
+
+// Branches with displacement relative to either instruction itself or, for
+// absolute forms, to zero
+fn ppc_branch(mode: Mode, addr: u64, bytes: &[u8]) -> Option<BranchInst> {
+    let b: [u8; 4] = bytes.get(0..4)?.try_into().ok()?;
+    let w = if mode.big_endian {
+        u32::from_be_bytes(b)
+    } else {
+        u32::from_le_bytes(b)
+    };
+
+    let off = match w >> 26 {
+        // b, bl, ba, bla
+        18 => sign_extend(w & 0x03ff_fffc, 26),
+        // bc and its forms
+        16 => sign_extend(w & 0xfffc, 16),
+        // blr
+        19 if w == 0x4e80_0020 => return Some(BranchInst::Return),
+        _ => return None,
+    };
+
+    let target = if w & 2 != 0 {
+        off as u64
+    } else {
+        addr.wrapping_add(off as u64)
+    };
+
+    // Conditional branch with link is mostly used to get current address
+    if w >> 26 == 18 && w & 1 != 0 {
+        Some(BranchInst::Call(target))
+    } else {
+        Some(BranchInst::Jump(target))
+    }
+}
+
+// Relative branches count halfwords, condition mask zero means nop
+fn s390x_branch(addr: u64, bytes: &[u8]) -> Option<BranchInst> {
+    if bytes == [0x07, 0xfe] {
+        // br %r14
+        return Some(BranchInst::Return);
+    }
+
+    let op = (*bytes.first()? as u16) << 4 | (*bytes.get(1)? & 0xf) as u16;
+    let mask = bytes[1] >> 4;
+
+    let off = match op {
+        0xa74..=0xa77 => i16::from_be_bytes(bytes.get(2..4)?.try_into().ok()?) as i64,
+        0xc04 | 0xc05 => i32::from_be_bytes(bytes.get(2..6)?.try_into().ok()?) as i64,
+        _ => return None,
+    };
+
+    let target = addr.wrapping_add((off * 2) as u64);
+
+    match op {
+        // bras, brasl
+        0xa75 | 0xc05 => Some(BranchInst::Call(target)),
+        // brc, brcl
+        0xa74 | 0xc04 if mask == 0 => None,
+        // brc, brcl, brct, brctg
+        _ => Some(BranchInst::Jump(target)),
+    }
+}
+
+fn sparc_word(bytes: &[u8]) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(0..4)?.try_into().ok()?))
+}
+
+// call, branches on integer and float conditions with 22 or 19 bit
+// displacement, and branches on register contents
+fn sparc_branch(addr: u64, bytes: &[u8]) -> Option<BranchInst> {
+    let w = sparc_word(bytes)?;
+    let target = |off: i64| addr.wrapping_add((off * 4) as u64);
+
+    match (w >> 30, (w >> 22) & 7) {
+        (1, _) => Some(BranchInst::Call(target(sign_extend(w & 0x3fff_ffff, 30)))),
+        (0, 2) | (0, 6) => Some(BranchInst::Jump(target(sign_extend(w & 0x3f_ffff, 22)))),
+        (0, 1) | (0, 5) => Some(BranchInst::Jump(target(sign_extend(w & 0x7_ffff, 19)))),
+        (0, 3) => {
+            let off = ((w >> 20) & 3) << 14 | (w & 0x3fff);
+
+            Some(BranchInst::Jump(target(sign_extend(off, 16))))
+        }
+        // ret, retl
+        (2, _) if w == 0x81c7_e008 || w == 0x81c3_e008 => Some(BranchInst::Return),
+        _ => None,
+    }
+}
+
+// Same as above plus jmpl and rett, which go through registers
+fn sparc_transfer(bytes: &[u8]) -> bool {
+    let w = match sparc_word(bytes) {
+        Some(w) => w,
+        None => return false,
+    };
+
+    match (w >> 30, (w >> 22) & 7) {
+        (1, _) => true,
+        (0, op2) => matches!(op2, 1 | 2 | 3 | 5 | 6),
+        (2, _) => matches!((w >> 19) & 0x3f, 0x38 | 0x39),
+        _ => false,
+    }
+}
+
+// Displacement is relative to the word after opcode. Absolute and PC relative
+// jsr and jmp are resolved as well
+fn m68k_branch(addr: u64, bytes: &[u8]) -> Option<BranchInst> {
+    let word = |i: usize| Some(u16::from_be_bytes(bytes.get(i..i + 2)?.try_into().ok()?));
+    let long = |i: usize| Some(u32::from_be_bytes(bytes.get(i..i + 4)?.try_into().ok()?));
+    let rel = |off: i64| addr.wrapping_add(2).wrapping_add(off as u64);
+    let op = word(0)?;
+
+    match op {
+        // rts, rte, rtr
+        0x4e75 | 0x4e73 | 0x4e77 => Some(BranchInst::Return),
+        0x4eb9 => Some(BranchInst::Call(long(2)? as u64)),
+        0x4ef9 => Some(BranchInst::Jump(long(2)? as u64)),
+        0x4eb8 => Some(BranchInst::Call(word(2)? as i16 as i32 as u32 as u64)),
+        0x4ef8 => Some(BranchInst::Jump(word(2)? as i16 as i32 as u32 as u64)),
+        0x4eba => Some(BranchInst::Call(rel(word(2)? as i16 as i64))),
+        0x4efa => Some(BranchInst::Jump(rel(word(2)? as i16 as i64))),
+        // dbcc
+        _ if op & 0xf0f8 == 0x50c8 => Some(BranchInst::Jump(rel(word(2)? as i16 as i64))),
+        // bra, bsr, bcc
+        _ if op >> 12 == 6 => {
+            let off = match op & 0xff {
+                0 => word(2)? as i16 as i64,
+                0xff => long(2)? as i32 as i64,
+                d => d as u8 as i8 as i64,
+            };
+
+            if (op >> 8) & 0xf == 1 {
+                Some(BranchInst::Call(rel(off)))
+            } else {
+                Some(BranchInst::Jump(rel(off)))
+            }
+        }
+        _ => None,
+    }
+}
+
+// Direct call, jump or return and target of the first two
+pub fn branch(cs: &Capstone, mode: Mode, inst: &Insn) -> Option<BranchInst> {
+    let (addr, bytes) = (inst.address(), inst.bytes());
+
+    match mode.arch {
+        Arch::Riscv => return riscv_branch(mode, addr, bytes),
+        Arch::Mips => return mips_branch(cs, inst),
+        Arch::Ppc => return ppc_branch(mode, addr, bytes),
+        Arch::S390x => return s390x_branch(addr, bytes),
+        Arch::Sparc => return sparc_branch(addr, bytes),
+        Arch::M68k => return m68k_branch(addr, bytes),
+        _ => {}
+    }
+
+    let detail = cs.insn_detail(inst).ok()?;
+    let target = || detail.arch_detail().operands().iter().find_map(imm);
+    let slot = || match mode.arch {
+        Arch::X86 | Arch::X86_64 => x86_target(cs, inst),
+        _ => None,
+    };
+
+    for i in detail.groups() {
+        match i {
+            InsnGroupId(CALL_INST) => {
+                return target()
+                    .map(BranchInst::Call)
+                    .or_else(|| slot().map(BranchInst::CallVia))
+            }
+            InsnGroupId(JUMP_INST) => {
+                return target()
+                    .map(BranchInst::Jump)
+                    .or_else(|| slot().map(BranchInst::JumpVia))
+            }
+            InsnGroupId(RET_INST) => return Some(BranchInst::Return),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+// Jump table with count entries of entry bytes at addr. Target of a case is
+// base plus the entry shifted left by shift, for absolute entries base is 0
+#[derive(Clone, Copy)]
+pub struct JumpTable {
+    pub addr: u64,
+    pub base: u64,
+    pub entry: usize,
+    pub signed: bool,
+    pub shift: u32,
+    pub count: usize,
+}
+
+// Jump tables are bounded by a compare before the load
+const MAX_CASES: u64 = 1024;
+
+// What is known while going through the code in order. AArch64 builds address
+// of a global out of ADRP and page offset in the add or load which follows it,
+// and switches load an entry of jump table and jump to address made of it
+pub struct Tracker {
+    arch: Arch,
+    // Registers known to hold an address
+    regs: Vec<(RegId, u64)>,
+    // Register compared with a constant and the largest index it allows
+    bound: Option<(RegId, u64)>,
+    // Table entry of which was loaded, waiting for the jump
+    table: Option<JumpTable>,
+    // Table of the last indirect jump
+    pub jump: Option<JumpTable>,
+}
+
+// W registers are the low halves of X ones, writing either changes both
+fn arm64_reg(reg: RegId) -> RegId {
+    use arch::arm64::Arm64Reg as R;
+
+    match (reg.0 as u32).checked_sub(R::ARM64_REG_W0) {
+        Some(n) if n <= 28 => RegId((R::ARM64_REG_X0 + n) as RegIdInt),
+        Some(29) => RegId(R::ARM64_REG_X29 as RegIdInt),
+        Some(30) => RegId(R::ARM64_REG_X30 as RegIdInt),
+        _ => reg,
+    }
+}
+
+// Same for 32-bit x86 registers and 64-bit ones they are part of
+fn x86_reg(reg: RegId) -> RegId {
+    use arch::x86::X86Reg as R;
+
+    const HALVES: [(u32, u32); 8] = [
+        (R::X86_REG_EAX, R::X86_REG_RAX),
+        (R::X86_REG_EBX, R::X86_REG_RBX),
+        (R::X86_REG_ECX, R::X86_REG_RCX),
+        (R::X86_REG_EDX, R::X86_REG_RDX),
+        (R::X86_REG_ESI, R::X86_REG_RSI),
+        (R::X86_REG_EDI, R::X86_REG_RDI),
+        (R::X86_REG_EBP, R::X86_REG_RBP),
+        (R::X86_REG_ESP, R::X86_REG_RSP),
+    ];
+
+    let id = reg.0 as u32;
+    let wide = match id.checked_sub(R::X86_REG_R8D) {
+        Some(n) if n < 8 => R::X86_REG_R8 + n,
+        _ => HALVES.iter().find(|h| h.0 == id).map_or(id, |h| h.1),
+    };
+
+    RegId(wide as RegIdInt)
+}
+
+impl Tracker {
+    pub fn new(arch: Arch) -> Self {
+        Self {
+            arch,
+            regs: Vec::new(),
+            bound: None,
+            table: None,
+            jump: None,
+        }
+    }
+
+    fn canonical(&self, reg: RegId) -> RegId {
+        match self.arch {
+            Arch::Arm64 => arm64_reg(reg),
+            Arch::X86 | Arch::X86_64 => x86_reg(reg),
+            _ => reg,
+        }
+    }
+
+    fn get(&self, reg: RegId) -> Option<u64> {
+        let reg = self.canonical(reg);
+
+        self.regs.iter().find(|p| p.0 == reg).map(|p| p.1)
+    }
+
+    fn set(&mut self, reg: RegId, value: Option<u64>) {
+        let reg = self.canonical(reg);
+
+        self.regs.retain(|p| p.0 != reg);
+        self.regs.extend(value.map(|v| (reg, v)));
+
+        if self.bound.is_some_and(|b| b.0 == reg) {
+            self.bound = None;
+        }
+    }
+
+    fn compare(&mut self, reg: RegId, bound: Option<u64>) {
+        self.bound = bound.map(|b| (self.canonical(reg), b));
+    }
+
+    fn bound_of(&self, reg: RegId) -> Option<u64> {
+        let reg = self.canonical(reg);
+
+        self.bound.filter(|b| b.0 == reg).map(|b| b.1)
+    }
+
+    // Code after unconditional jump, call or return is reached from
+    // somewhere else, with registers holding anything
+    fn end_block(&mut self) {
+        self.regs.clear();
+        self.bound = None;
+        self.table = None;
+    }
+
+    fn load_table(&mut self, addr: u64, base: u64, entry: usize, signed: bool) {
+        self.table = self.bound.filter(|b| b.1 < MAX_CASES).map(|b| JumpTable {
+            addr,
+            base,
+            entry,
+            signed,
+            shift: 0,
+            count: b.1 as usize + 1,
+        });
+    }
+}
+
+fn arm64_target(cs: &Capstone, inst: &Insn, t: &mut Tracker) -> Option<u64> {
+    use arch::arm64::Arm64OperandType as Op;
+
+    let detail = cs.insn_detail(inst).ok()?;
+    let ops: Vec<Op> = detail
+        .arch_detail()
+        .operands()
+        .into_iter()
+        .filter_map(|op| match op {
+            ArchOperand::Arm64Operand(op) => Some(op.op_type),
+            _ => None,
+        })
+        .collect();
+
+    let mnemonic = inst.mnemonic().unwrap_or("");
+    let op_str = inst.op_str().unwrap_or("");
+    // New value of the first operand
+    let mut value = None;
+    // Bound of index copied into the first operand
+    let mut kept = None;
+
+    let target = match (mnemonic, ops.as_slice()) {
+        ("adrp", [Op::Reg(_), Op::Imm(page)]) => {
+            value = Some(*page as u64);
+            None
+        }
+        ("adr", [Op::Reg(_), Op::Imm(x)]) => {
+            value = Some(*x as u64);
+            value
+        }
+        ("add", [Op::Reg(_), Op::Reg(s), Op::Imm(off)]) => {
+            value = t.get(*s).map(|p| p.wrapping_add(*off as u64));
+            value
+        }
+        // Table entry added to address of the first case, like
+        // `add x0, x1, w0, sxtb #2`
+        ("add", [Op::Reg(_), Op::Reg(s), Op::Reg(_)]) => {
+            if let (Some(base), Some(table)) = (t.get(*s), t.table.as_mut()) {
+                table.base = base;
+                table.signed |= op_str.contains("sxt");
+                table.shift = match op_str.rsplit_once('#') {
+                    Some((_, n)) => n.trim().parse().unwrap_or(0),
+                    None => 0,
+                };
+            }
+            None
+        }
+        ("cmp", [Op::Reg(r), Op::Imm(x)]) => {
+            t.compare(*r, Some(*x as u64));
+            None
+        }
+        ("mov", [Op::Reg(_), Op::Reg(s)]) => {
+            kept = t.bound_of(*s);
+            None
+        }
+        ("br", [Op::Reg(_)]) => {
+            t.jump = t.table.take().filter(|table| table.base != 0);
+            None
+        }
+        // Literal load
+        (m, [Op::Reg(_), Op::Imm(x)]) if m.starts_with("ldr") => Some(*x as u64),
+        (m, [.., Op::Mem(mem)]) if m.starts_with("ld") || m.starts_with("st") => {
+            let addr = t.get(mem.base())?;
+
+            // Entry of table indexed by register
+            if m.starts_with("ldr") && mem.index().0 != 0 {
+                let entry = match m.trim_start_matches("ldrs").trim_start_matches("ldr") {
+                    "b" => 1,
+                    "h" => 2,
+                    _ => 4,
+                };
+
+                t.load_table(addr, 0, entry, m.starts_with("ldrs"));
+            }
+
+            Some(addr.wrapping_add(mem.disp() as i64 as u64))
+        }
+        _ => None,
+    };
+
+    // The first operand is overwritten by everything but stores, compares and
+    // branches
+    let writes = !(mnemonic.starts_with("st")
+        || mnemonic.starts_with('b')
+        || mnemonic.starts_with("cb")
+        || mnemonic.starts_with("tb")
+        || matches!(mnemonic, "cmp" | "cmn" | "tst" | "ccmp" | "ccmn"));
+
+    if let (true, Some(Op::Reg(r))) = (writes, ops.first()) {
+        t.set(*r, value);
+
+        if kept.is_some() {
+            t.compare(*r, kept);
+        }
+    }
+
+    if matches!(mnemonic, "b" | "bl" | "blr" | "br" | "ret") {
+        t.end_block();
+    }
+
+    target
+}
+
+// RIP-relative operands are relative to the end of instruction, 32-bit code
+// refers to memory by absolute displacement
+fn x86_target(cs: &Capstone, inst: &Insn) -> Option<u64> {
+    let detail = cs.insn_detail(inst).ok()?;
+    let end = inst.address() + inst.bytes().len() as u64;
+
+    detail
+        .arch_detail()
+        .operands()
+        .into_iter()
+        .find_map(|op| match op {
+            ArchOperand::X86Operand(op) => match op.op_type {
+                arch::x86::X86OperandType::Mem(mem)
+                    if cs.reg_name(mem.base()).as_deref() == Some("rip") =>
+                {
+                    Some(end.wrapping_add(mem.disp() as u64))
+                }
+                arch::x86::X86OperandType::Mem(mem)
+                    if mem.base().0 == 0 && mem.index().0 == 0 && mem.segment().0 == 0 =>
+                {
+                    Some(mem.disp() as u32 as u64)
+                }
+                _ => None,
+            },
+            _ => None,
+        })
+}
+
+// Operand order follows syntax, so x86 operands are looked up by their kind
+fn x86_track(cs: &Capstone, inst: &Insn, t: &mut Tracker) -> Option<u64> {
+    use arch::x86::X86Insn as I;
+    use arch::x86::X86Operand;
+    use arch::x86::X86OperandType as Op;
+
+    let target = x86_target(cs, inst);
+    let detail = cs.insn_detail(inst).ok()?;
+    let operands: Vec<X86Operand> = detail
+        .arch_detail()
+        .operands()
+        .into_iter()
+        .filter_map(|op| match op {
+            ArchOperand::X86Operand(op) => Some(op),
+            _ => None,
+        })
+        .collect();
+    let ops: Vec<Op> = operands.iter().map(|op| op.op_type.clone()).collect();
+
+    // Mnemonics have size suffixes in AT&T syntax
+    let id = inst.id().0;
+    let is = |i: I| id == i as u32;
+
+    let reg = ops.iter().find_map(|op| match op {
+        Op::Reg(r) => Some(*r),
+        _ => None,
+    });
+    let mem = ops.iter().find_map(|op| match op {
+        Op::Mem(m) => Some(*m),
+        _ => None,
+    });
+    let imm = ops.iter().find_map(|op| match op {
+        Op::Imm(x) => Some(*x as u64),
+        _ => None,
+    });
+
+    // Register operand instruction reads or writes, they are in syntax order
+    let access = |write: bool| {
+        operands
+            .iter()
+            .find_map(|op| match (&op.op_type, op.access) {
+                (Op::Reg(r), Some(a)) if a.is_writable() == write => Some(*r),
+                _ => None,
+            })
+    };
+
+    // Index copied to another register keeps its bound, like `mov eax, edi`
+    let kept = match access(false) {
+        Some(r) if is(I::X86_INS_MOV) => t.bound_of(r),
+        _ => None,
+    };
+
+    match (reg, mem) {
+        (Some(r), None) if is(I::X86_INS_CMP) => t.compare(r, imm),
+        // Table of offsets from its own address, like
+        // `movsxd rax, dword ptr [rdx + rdi*4]`
+        (Some(_), Some(m)) if is(I::X86_INS_MOVSXD) && m.index().0 != 0 && m.scale() == 4 => {
+            if let Some(addr) = t.get(m.base()) {
+                t.load_table(addr, addr, 4, true);
+            }
+        }
+        (Some(_), None) if is(I::X86_INS_JMP) => t.jump = t.table.take(),
+        // Table of addresses, like `jmp qword ptr [rax*8 + table]`
+        (None, Some(m)) if is(I::X86_INS_JMP) && m.base().0 == 0 && m.index().0 != 0 => {
+            t.load_table(m.disp() as u64, 0, m.scale() as usize, false);
+            t.jump = t.table.take();
+        }
+        _ => {}
+    }
+
+    // Whatever register is written, value of it is not known anymore
+    let writes = !(is(I::X86_INS_CMP)
+        || is(I::X86_INS_TEST)
+        || is(I::X86_INS_PUSH)
+        || inst.mnemonic().unwrap_or("").starts_with('j'));
+
+    if writes {
+        for op in &ops {
+            if let Op::Reg(r) = op {
+                t.set(*r, None);
+            }
+        }
+    }
+
+    if let (true, Some(r)) = (is(I::X86_INS_LEA), reg) {
+        t.set(r, target);
+    }
+
+    if let (Some(_), Some(r)) = (kept, access(true)) {
+        t.compare(r, kept);
+    }
+
+    if is(I::X86_INS_JMP) || is(I::X86_INS_CALL) || is(I::X86_INS_RET) {
+        t.end_block();
+    }
+
+    target
+}
+
+// Relocated operand has no meaningful value until link time, relocation tells
+// what instruction refers to instead
+pub fn reloc(bin: &dyn Binary, inst: &Inst) -> Option<Reloc> {
+    bin.reloc_at(inst.addr, inst.bytes.len())
+}
+
+// Where jump goes, encoded target of relocated one is only a placeholder
+pub fn jump_target(bin: &dyn Binary, inst: &Inst) -> Option<u64> {
+    match (inst.branch, reloc(bin, inst)) {
+        (Some(BranchInst::Jump(_)), Some(r)) => r.target,
+        (Some(BranchInst::Jump(x)), None) => Some(x),
+        _ => None,
+    }
+}
+
+// Address of data instruction refers to
+pub fn data_target(cs: &Capstone, mode: Mode, inst: &Insn, t: &mut Tracker) -> Option<u64> {
+    match mode.arch {
+        Arch::Arm64 => arm64_target(cs, inst, t),
+        Arch::X86 | Arch::X86_64 => x86_track(cs, inst, t),
+        _ => None,
+    }
+}
+
+// Case targets of jump table read from the image
+pub fn table_targets(bin: &dyn Binary, mode: Mode, table: &JumpTable) -> Vec<u64> {
+    let bytes = match bin.bytes_at(table.addr) {
+        Some(b) => b,
+        None => return Vec::new(),
+    };
+    let bits = table.entry as u32 * 8;
+
+    bytes
+        .chunks_exact(table.entry)
+        .take(table.count)
+        .map(|e| {
+            let raw = if mode.big_endian {
+                e.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64)
+            } else {
+                e.iter().rev().fold(0u64, |acc, b| (acc << 8) | *b as u64)
+            };
+
+            let value = if table.signed && bits < 64 {
+                ((raw << (64 - bits)) as i64 >> (64 - bits)) as u64
+            } else {
+                raw
+            };
+
+            table.base.wrapping_add(value << table.shift)
+        })
+        .collect()
+}
+
+// Branch made of several instructions, where inst is the last one
+pub fn branch_pair(mode: Mode, prev: &Inst, inst: &Inst) -> Option<BranchInst> {
+    match mode.arch {
+        Arch::Riscv => riscv_pair(prev, inst),
+        _ => None,
+    }
+}
+
+// MIPS and SPARC execute instruction after branch before the branch takes
+// effect, except for MIPS compact branches of newer revisions
+pub fn has_delay_slot(cs: &Capstone, mode: Mode, inst: &Insn) -> bool {
+    match mode.arch {
+        Arch::Mips => !inst.mnemonic().unwrap_or("").ends_with('c') && mips_transfer(cs, inst),
+        Arch::Sparc => sparc_transfer(inst.bytes()),
+        _ => false,
+    }
+}
+
+// Stripped binaries only know a few function starts, everything called from
+// them directly is a function as well
+pub fn discover_functions(bin: &mut dyn Binary, dec: &Decoder) {
+    let mut work: Vec<Function> = bin.function_names();
+
+    while let Some(f) = work.pop() {
+        let calls: Vec<u64> = dec
+            .decode(bin, &f)
+            .iter()
+            .filter_map(|i| match i.branch {
+                Some(BranchInst::Call(x)) => Some(x),
+                _ => None,
+            })
+            .collect();
+
+        for c in calls {
+            if bin.function_by_addr(c).is_none() {
+                if let Some(new) = bin.add_function(c) {
+                    work.push(new);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::Section;
+    use crate::decoder::Syntax;
+
+    // Code at 0x1000 and data it refers to at 0x2000
+    struct Image {
+        arch: Arch,
+        code: usize,
+        bytes: Vec<u8>,
+    }
+
+    impl Binary for Image {
+        fn arch(&self) -> Arch {
+            self.arch
+        }
+
+        fn function_by_addr(&self, _addr: u64) -> Option<Function> {
+            None
+        }
+
+        fn function_names(&self) -> Vec<Function> {
+            Vec::new()
+        }
+
+        fn sections(&self) -> Vec<Section> {
+            Vec::new()
+        }
+
+        fn func_code(&self, _f: &Function) -> (&[u8], u64) {
+            (&self.bytes[..self.code], 0x1000)
+        }
+
+        fn bytes_at(&self, addr: u64) -> Option<&[u8]> {
+            self.bytes.get(addr.checked_sub(0x1000)? as usize..)
+        }
+    }
+
+    fn decode(arch: Arch, code: &[u8], data: &[u8]) -> Vec<Inst> {
+        let mut bytes = code.to_vec();
+        bytes.resize(0x1000, 0);
+        bytes.extend(data);
+
+        let image = Image {
+            arch,
+            code: code.len(),
+            bytes,
+        };
+        let dec = Decoder::new(Mode::new(arch), Syntax::Intel).unwrap();
+
+        dec.decode(&image, &Function::new("f".to_owned(), 0x1000, code.len()))
+    }
+
+    fn targets(arch: Arch, code: &[u8]) -> Vec<Option<u64>> {
+        decode(arch, code, &[]).iter().map(|i| i.target).collect()
+    }
+
+    fn rv32() -> Mode {
+        Mode {
+            bits64: false,
+            ..Mode::new(Arch::Riscv)
+        }
+    }
+
+    fn jump(mode: Mode, addr: u64, h: u16) -> Option<u64> {
+        match riscv_branch(mode, addr, &h.to_le_bytes()) {
+            Some(BranchInst::Jump(x)) => Some(x),
+            _ => None,
+        }
+    }
+
+    fn call(mode: Mode, addr: u64, h: u16) -> Option<u64> {
+        match riscv_branch(mode, addr, &h.to_le_bytes()) {
+            Some(BranchInst::Call(x)) => Some(x),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn riscv_compressed_jumps() {
+        assert_eq!(jump(rv32(), 0x4, 0xbff5), Some(0x0));
+        assert_eq!(jump(rv32(), 0xc, 0xa821), Some(0x24));
+    }
+
+    #[test]
+    fn riscv_compressed_calls() {
+        assert_eq!(call(rv32(), 0x2, 0x200d), Some(0x24));
+        assert_eq!(call(rv32(), 0x24, 0x3ff1), Some(0x0));
+        // Same encoding is c.addiw on RV64
+        assert!(riscv_branch(Mode::new(Arch::Riscv), 0x2, &[0x0d, 0x20]).is_none());
+    }
+
+    #[test]
+    fn riscv_compressed_branches() {
+        // c.bnez a0 and c.beqz a0, both back to 0
+        assert_eq!(jump(rv32(), 0xa, 0xf97d), Some(0x0));
+        assert_eq!(jump(rv32(), 0xe, 0xd96d), Some(0x0));
+    }
+
+    #[test]
+    fn riscv_compressed_return() {
+        assert!(matches!(
+            riscv_branch(rv32(), 0x20, &[0x82, 0x80]),
+            Some(BranchInst::Return)
+        ));
+        // c.jr t1 is not a return
+        assert!(riscv_branch(rv32(), 0x20, &[0x02, 0x83]).is_none());
+    }
+
+    #[test]
+    fn m68k_absolute_short_targets() {
+        let call = |bytes: &[u8]| match m68k_branch(0x1000, bytes) {
+            Some(BranchInst::Call(x)) => Some(x),
+            _ => None,
+        };
+
+        // jsr (0x7000).w and jsr (0xfffffffe).w, short address is sign
+        // extended to 32 bits
+        assert_eq!(call(&[0x4e, 0xb8, 0x70, 0x00]), Some(0x7000));
+        assert_eq!(call(&[0x4e, 0xb8, 0xff, 0xfe]), Some(0xffff_fffe));
+        // bsr.w back to 0x1000
+        assert_eq!(call(&[0x61, 0x00, 0xff, 0xfe]), Some(0x1000));
+    }
+
+    #[test]
+    fn arm64_adrp_pairs() {
+        #[rustfmt::skip]
+        let code = [
+            0x01, 0x00, 0x00, 0xb0, // adrp x1, 0x2000
+            0x20, 0x80, 0x00, 0x91, // add x0, x1, #0x20
+            0x22, 0x0c, 0x40, 0xf9, // ldr x2, [x1, #0x18]
+            0xe1, 0x03, 0x03, 0x2a, // mov w1, w3
+            0x20, 0x80, 0x00, 0x91, // add x0, x1, #0x20
+            0x01, 0x00, 0x00, 0xb0, // adrp x1, 0x2000
+            0x40, 0x00, 0x00, 0x94, // bl 0x1118
+            0x20, 0x80, 0x00, 0x91, // add x0, x1, #0x20
+        ];
+
+        // Page is forgotten once W half of its register is written, and
+        // after a call
+        assert_eq!(
+            targets(Arch::Arm64, &code),
+            [
+                None,
+                Some(0x2020),
+                Some(0x2018),
+                None,
+                None,
+                None,
+                None,
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn x86_rip_relative_targets() {
+        #[rustfmt::skip]
+        let code = [
+            0x48, 0x8d, 0x3d, 0xaa, 0x0e, 0x00, 0x00, // lea rdi, [rip + 0xeaa]
+            0x48, 0x8b, 0x05, 0x10, 0x00, 0x00, 0x00, // mov rax, qword ptr [rip + 0x10]
+            0x8b, 0x40, 0x08, // mov eax, dword ptr [rax + 8]
+        ];
+
+        assert_eq!(
+            targets(Arch::X86_64, &code),
+            [Some(0x1eb1), Some(0x101e), None]
+        );
+    }
+
+    fn cases(arch: Arch, code: &[u8], data: &[u8]) -> Vec<u64> {
+        let insts = decode(arch, code, data);
+
+        insts.last().map(|i| i.cases.clone()).unwrap_or_default()
+    }
+
+    #[test]
+    fn x86_jump_table() {
+        #[rustfmt::skip]
+        let code = [
+            0x83, 0xff, 0x03, // cmp edi, 3
+            0x77, 0x3e, // ja 0x1043
+            0x89, 0xf8, // mov eax, edi
+            0x48, 0x8d, 0x0d, 0xf2, 0x0f, 0x00, 0x00, // lea rcx, [rip + 0xff2]
+            0x48, 0x63, 0x04, 0x81, // movsxd rax, dword ptr [rcx + rax*4]
+            0x48, 0x01, 0xc8, // add rax, rcx
+            0xff, 0xe0, // jmp rax
+        ];
+        let table: Vec<u8> = [-0xffb, -0xff2, -0xfee, -0xfeb]
+            .iter()
+            .flat_map(|e: &i32| e.to_le_bytes())
+            .collect();
+
+        assert_eq!(
+            cases(Arch::X86_64, &code, &table),
+            [0x1005, 0x100e, 0x1012, 0x1015]
+        );
+
+        // Index overwritten after the compare
+        let mut written = code;
+        written[5..7].copy_from_slice(&[0x89, 0xf7]); // mov edi, esi
+        written[17] = 0xb9; // movsxd rax, dword ptr [rcx + rdi*4]
+        assert!(cases(Arch::X86_64, &written, &table).is_empty());
+
+        // Compare in a block the table load is not reached from
+        let mut jumped = code;
+        jumped[3..5].copy_from_slice(&[0xeb, 0x00]); // jmp 0x1005
+        assert!(cases(Arch::X86_64, &jumped, &table).is_empty());
+    }
+
+    #[test]
+    fn arm64_jump_table() {
+        #[rustfmt::skip]
+        let code = [
+            0x1f, 0x0c, 0x00, 0x71, // cmp w0, #3
+            0x08, 0x02, 0x00, 0x54, // b.hi 0x1044
+            0x01, 0x00, 0x00, 0xb0, // adrp x1, 0x2000
+            0x21, 0x80, 0x00, 0x91, // add x1, x1, #0x20
+            0x20, 0x48, 0x60, 0x38, // ldrb w0, [x1, w0, uxtw]
+            0x41, 0x00, 0x00, 0x10, // adr x1, 0x101c
+            0x20, 0x88, 0x20, 0x8b, // add x0, x1, w0, sxtb #2
+            0x00, 0x00, 0x1f, 0xd6, // br x0
+        ];
+        let mut table = vec![0; 0x20];
+        table.extend([0, 1, 2, 0xff]);
+
+        assert_eq!(
+            cases(Arch::Arm64, &code, &table),
+            [0x101c, 0x1020, 0x1024, 0x1018]
+        );
+
+        // X half of the compared register written
+        let mut written = code;
+        written[4..8].copy_from_slice(&[0xe0, 0x03, 0x02, 0xaa]); // mov x0, x2
+        assert!(cases(Arch::Arm64, &written, &table).is_empty());
+
+        let mut jumped = code;
+        jumped[4..8].copy_from_slice(&[0x01, 0x00, 0x00, 0x14]); // b 0x1008
+        assert!(cases(Arch::Arm64, &jumped, &table).is_empty());
+    }
+}
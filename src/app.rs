@@ -1,16 +1,21 @@
 use super::term::cmd::CommandLine;
-use crate::elf::{Arch, Elf};
+use crate::analysis;
+use crate::binary::{Arch, Binary};
+use crate::debug_file::DebugSearch;
+use crate::decoder::{Decoder, Syntax};
+use crate::macho::FatSlice;
 use crate::term::events::KeyboardEvent;
+use crate::term::frames::archive_list::ArchiveList;
+use crate::term::frames::call_tree::CallTree;
+use crate::term::frames::error_info::ErrorInfo;
+use crate::term::frames::func_asm::FuncAsm;
 use crate::term::frames::func_list::*;
-use capstone::prelude::*;
-
-
-use tui::widgets::ListState;
-
-
+use crate::term::frames::slice_list::SliceList;
+use crate::term::frames::xref_list::XrefList;
 use crate::term::frames::*;
-
+use crate::xref::Xrefs;
 use std::collections::LinkedList;
+use tui::widgets::ListState;
 
 #[derive(Copy, Clone, PartialEq)]
 pub enum State {
@@ -20,13 +25,15 @@ pub enum State {
 
 pub struct App {
     frame_list: LinkedList<(ItemType, ListState)>, // Like a cache for now
-    elf: Elf,
+    bin: Option<Box<dyn Binary>>,
 
     /* I am done with rust lifetimes. I need to keep references
      * to instructions in Disassembly frame, but poisoning whole struct
      * with lifetime makes things x10 harder.
      */
-    cs: &'static Capstone,
+    dec: Option<&'static Decoder>,
+    xrefs: Option<Xrefs>,
+    syntax: Syntax,
     state: State,
 
     pub cmd: CommandLine,
@@ -39,12 +46,17 @@ impl App {
         assert!(self.help_requested);
 
         vec![
-            "h, j, k       --    back, down, up",
+            "h, j, k, l    --    back, down, up, in; move between blocks in graph",
             "q             --    exit",
             "F1            --    help",
             "ESC           --    exit help or exit find",
-            "Enter         --    follow call or jump",
+            "Enter         --    follow call or jump, list cases of jump table",
             "/             --    find",
+            "f             --    define function at cursor",
+            "s             --    switch x86 syntax (Intel, AT&T, MASM)",
+            "g             --    graph of basic blocks, Enter goes back to listing",
+            "t             --    call tree of function, in tree callers of selected one",
+            "x             --    references to function, or to what instruction refers to",
         ]
     }
 
@@ -78,6 +90,23 @@ impl App {
             return false;
         }
 
+        if let (ItemType::FunctionGraph(g), state) = self.active_main_frame() {
+            if g.navigate(&e, state) {
+                return false;
+            }
+        }
+
+        if let (Some((ItemType::CallTree(t), state)), Some(bin), Some(dec), Some(x)) = (
+            self.frame_list.front_mut(),
+            self.bin.as_ref(),
+            self.dec,
+            self.xrefs.as_ref(),
+        ) {
+            if t.navigate(&e, state, bin.as_ref(), dec, x) {
+                return false;
+            }
+        }
+
         match e {
             KeyboardEvent::Next => self.next_elem(),
             KeyboardEvent::Prev => self.prev_elem(),
@@ -85,12 +114,18 @@ impl App {
                 self.state = State::Control;
                 self.go_in();
             }
-            KeyboardEvent::PrevFrame => self.prev_frame(),
+            KeyboardEvent::Right => self.go_in(),
+            KeyboardEvent::Left => self.prev_frame(),
             KeyboardEvent::Exit => return true,
             KeyboardEvent::Key(c) => self.input_char(Some(c)),
             KeyboardEvent::Delete => self.input_char(None),
             KeyboardEvent::Help => self.help_requested = true,
             KeyboardEvent::HelpEnd => self.help_requested = false,
+            KeyboardEvent::DefineFunction => self.define_function(),
+            KeyboardEvent::SwitchSyntax => self.switch_syntax(),
+            KeyboardEvent::Xrefs => self.show_xrefs(),
+            KeyboardEvent::Graph => self.show_graph(),
+            KeyboardEvent::CallTree => self.show_call_tree(),
             _ => {}
         }
 
@@ -106,45 +141,73 @@ impl App {
         self.state
     }
 
-    pub fn new(elf: Elf) -> Option<Self> {
-        let funcs = elf.function_names();
-        let list = FuncList::new(funcs);
-
-        let cs = match elf.arch() {
-            Arch::X86 => Capstone::new()
-                .x86()
-                .mode(arch::x86::ArchMode::Mode32)
-                .detail(true)
-                .build()
-                .ok()?,
-            Arch::X86_64 => Capstone::new()
-                .x86()
-                .mode(arch::x86::ArchMode::Mode32)
-                .detail(true)
-                .build()
-                .ok()?,
-            Arch::Arm64 => Capstone::new()
-                .arm64()
-                .mode(arch::arm64::ArchMode::Arm)
-                .detail(true)
-                .build()
-                .ok()?,
-            Arch::Arm => Capstone::new().arm().detail(true).build().ok()?,
-            Arch::Riscv => Capstone::new().riscv().detail(true).build().ok()?,
-            Arch::Mips => Capstone::new().mips().detail(true).build().ok()?,
-        };
-
-        let mut s = Self {
-            cs: Box::leak(Box::new(cs)),
+    fn empty(syntax: Syntax) -> Self {
+        Self {
+            dec: None,
+            xrefs: None,
+            syntax,
+            bin: None,
             frame_list: LinkedList::new(),
             cmd: CommandLine::new(),
             state: State::Control,
-            elf,
             help_requested: false,
+        }
+    }
+
+    // Makes bin the binary all frames work on and shows its functions, or
+    // its members for archives
+    fn open(&mut self, mut bin: Box<dyn Binary>) {
+        let dec = match Decoder::new(bin.mode(), self.syntax) {
+            Some(dec) => dec,
+            None => {
+                let reason = if bin.arch() == Arch::Unknown {
+                    "Architecture of the file is not supported"
+                } else {
+                    "Failed to create disassembler for the file"
+                };
+
+                self.frame_list.push_front((
+                    ItemType::ErrorInfo(ErrorInfo::new(vec![
+                        reason.to_owned(),
+                        "Decoder can be picked with --arch".to_owned(),
+                    ])),
+                    ListState::default().with_selected(Some(0)),
+                ));
+                return;
+            }
+        };
+
+        if bin.stripped() {
+            analysis::discover_functions(bin.as_mut(), &dec);
+        }
+
+        let members = bin.members();
+        let list = if members.is_empty() {
+            ItemType::FunctionList(FuncList::new(bin.function_names()))
+        } else {
+            ItemType::ArchiveList(ArchiveList::new(members))
         };
 
-        s.add_front_frame(ItemType::FunctionList(list));
-        Some(s)
+        self.xrefs = Some(Xrefs::new(bin.as_ref(), &dec));
+        self.dec = Some(Box::leak(Box::new(dec)));
+        self.bin = Some(bin);
+        self.frame_list
+            .push_front((list, ListState::default().with_selected(Some(0))));
+    }
+
+    pub fn new(bin: Box<dyn Binary>, syntax: Syntax) -> Self {
+        let mut s = Self::empty(syntax);
+
+        s.open(bin);
+        s
+    }
+
+    // Universal binary: user has to pick a slice first
+    pub fn with_slices(slices: Vec<FatSlice>, search: DebugSearch, syntax: Syntax) -> Self {
+        let mut s = Self::empty(syntax);
+
+        s.add_front_frame(ItemType::SliceList(SliceList::new(slices, search)));
+        s
     }
 
     pub fn next_elem(&mut self) {
@@ -171,13 +234,226 @@ impl App {
         // We know it exist
         let (fr, state) = self.frame_list.front_mut().unwrap();
 
-        let new = fr.go_in(&self.elf, self.cs, state);
+        if let ItemType::SliceList(l) = fr {
+            if let Some(bin) = l.load(state) {
+                self.open(bin);
+            }
+
+            return;
+        }
+
+        // Picking a case or a block moves cursor of listing they came from
+        let target = match fr {
+            ItemType::CaseList(l) => Some(l.target(state)),
+            ItemType::FunctionGraph(g) => Some(g.addr()),
+            _ => None,
+        };
+
+        if let Some(target) = target {
+            self.frame_list.pop_front();
+            if let (Some(addr), Some((ItemType::FunctionDisas(f), state))) =
+                (target, self.frame_list.front_mut())
+            {
+                f.select_addr(addr, state);
+            }
+
+            return;
+        }
+
+        if let ItemType::XrefList(l) = fr {
+            if let (Some((f, addr)), Some(bin), Some(dec)) =
+                (l.site(state), self.bin.as_ref(), self.dec)
+            {
+                let mut state = ListState::default().with_selected(Some(0));
+                let mut new = FuncAsm::new(f, bin.as_ref(), dec);
+
+                new.select_addr(addr, &mut state);
+                self.frame_list
+                    .push_front((ItemType::FunctionDisas(new), state));
+            }
+
+            return;
+        }
+
+        let new = match (self.bin.as_ref(), self.dec) {
+            (Some(bin), Some(dec)) => fr.go_in(bin.as_ref(), dec, state),
+            _ => None,
+        };
+
         if let Some(s) = new {
             self.frame_list
                 .push_front((s, ListState::default().with_selected(Some(0))));
         }
     }
 
+    // Creates function starting at selected instruction and opens it
+    pub fn define_function(&mut self) {
+        let (fr, state) = self.frame_list.front().unwrap();
+
+        let addr = match fr {
+            ItemType::FunctionDisas(f) => f.addr_at(state),
+            _ => None,
+        };
+
+        let (addr, bin, dec) = match (addr, self.bin.as_mut(), self.dec) {
+            (Some(addr), Some(bin), Some(dec)) => (addr, bin, dec),
+            _ => return,
+        };
+
+        if let Some(f) = bin.add_function(addr) {
+            if let Some(x) = self.xrefs.as_mut() {
+                x.add_function(bin.as_ref(), dec, &f);
+            }
+
+            let list = self
+                .frame_list
+                .iter_mut()
+                .rev()
+                .find(|x| matches!(x.0, ItemType::FunctionList(_)));
+
+            if let Some(l) = list {
+                l.0 = ItemType::FunctionList(FuncList::new(bin.function_names()));
+            }
+
+            let new = FuncAsm::new(f, bin.as_ref(), dec);
+            self.frame_list.push_front((
+                ItemType::FunctionDisas(new),
+                ListState::default().with_selected(Some(0)),
+            ));
+        }
+    }
+
+    pub fn show_graph(&mut self) {
+        let (fr, state) = self.frame_list.front().unwrap();
+
+        if let Some(new) = match fr {
+            ItemType::FunctionDisas(f) => f.graph(state),
+            _ => None,
+        } {
+            let state = new.state();
+
+            self.frame_list
+                .push_front((ItemType::FunctionGraph(new), state));
+        }
+    }
+
+    // Calls from selected function, in call tree the same tree inverted
+    pub fn show_call_tree(&mut self) {
+        let (bin, dec, xrefs) = match (self.bin.as_ref(), self.dec, self.xrefs.as_ref()) {
+            (Some(bin), Some(dec), Some(x)) => (bin.as_ref(), dec, x),
+            _ => return,
+        };
+        let (fr, state) = self.frame_list.front().unwrap();
+
+        let new = match fr {
+            ItemType::FunctionList(l) => l
+                .function_at(state)
+                .map(|f| CallTree::new(f, false, bin, dec, xrefs)),
+            ItemType::FunctionDisas(f) => {
+                Some(CallTree::new(f.function().clone(), false, bin, dec, xrefs))
+            }
+            ItemType::CallTree(t) => {
+                let new = t.inverted(state, bin, dec, xrefs);
+
+                self.frame_list.pop_front();
+                Some(new)
+            }
+            _ => None,
+        };
+
+        if let Some(new) = new {
+            self.frame_list.push_front((
+                ItemType::CallTree(new),
+                ListState::default().with_selected(Some(0)),
+            ));
+        }
+    }
+
+    // Lists instructions referencing selected function, or in listing the
+    // target of selected instruction, falling back to instruction itself
+    pub fn show_xrefs(&mut self) {
+        let (fr, state) = self.frame_list.front().unwrap();
+
+        let (addr, bin, xrefs) = match (fr, self.bin.as_ref(), self.xrefs.as_ref()) {
+            (ItemType::FunctionList(l), Some(bin), Some(x)) => match l.function_at(state) {
+                Some(f) => (f.addr(), bin, x),
+                None => return,
+            },
+            (ItemType::FunctionDisas(f), Some(bin), Some(x)) => {
+                match f
+                    .target_at(bin.as_ref(), state)
+                    .or_else(|| f.addr_at(state))
+                {
+                    Some(addr) => (addr, bin, x),
+                    None => return,
+                }
+            }
+            _ => return,
+        };
+
+        // Calls through GOT are counted at the stub of import
+        let (addr, import) = match bin.import_by_got(addr) {
+            Some(i) => match i.stub {
+                Some(stub) => (stub.addr(), Some(stub.name().clone())),
+                None => (addr, Some(format!("{}@{}", i.name, i.table))),
+            },
+            None => (addr, None),
+        };
+
+        let name = import.unwrap_or_else(|| match bin.function_by_addr(addr) {
+            Some(f) if f.addr() == addr => f.name().clone(),
+            _ => match bin.symbol_at(addr) {
+                Some((name, 0)) => name,
+                Some((name, off)) => format!("{}+0x{:x}", name, off),
+                None => format!("0x{:x}", addr),
+            },
+        });
+
+        let new = XrefList::new(name, xrefs.to(addr));
+        self.frame_list.push_front((
+            ItemType::XrefList(new),
+            ListState::default().with_selected(Some(0)),
+        ));
+    }
+
+    // Every open listing is decoded again, so going back shows new syntax too.
+    // Graphs are built again from the listing they were opened from
+    pub fn switch_syntax(&mut self) {
+        let (bin, dec) = match (self.bin.as_ref(), self.dec) {
+            (Some(bin), Some(dec)) => (bin, dec),
+            _ => return,
+        };
+
+        if !dec.set_syntax(self.syntax.next()) {
+            return;
+        }
+
+        self.syntax = self.syntax.next();
+
+        let mut listing = None;
+
+        for (fr, state) in self.frame_list.iter_mut().rev() {
+            match fr {
+                ItemType::FunctionDisas(f) => {
+                    f.reload(bin.as_ref(), dec, state);
+                    listing = Some(&*f);
+                }
+                ItemType::FunctionGraph(g) => {
+                    let new = match (listing, g.addr()) {
+                        (Some(f), Some(addr)) => f.graph_at(addr),
+                        _ => None,
+                    };
+
+                    if let Some(new) = new {
+                        *state = new.state();
+                        *g = new;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
     pub fn input_char(&mut self, c: Option<char>) {
         self.cmd.proccess_char(c);
 
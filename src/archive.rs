@@ -0,0 +1,244 @@
+use crate::binary::{Arch, Binary, CodeKind, Function, Member, Mode, Reloc, Section};
+use crate::dwarf::FunctionDebugInfo;
+use crate::elf::Elf;
+use object::read::archive::ArchiveFile;
+use object::FileKind;
+
+// Object file inside of archive. Every object gets its own address range,
+// so functions of different members never collide
+struct Object {
+    name: String,
+    symbols: usize,
+    elf: Option<Elf>,
+    start: u64,
+    end: u64,
+}
+
+pub struct Archive {
+    objects: Vec<Object>,
+}
+
+const HEADER_SIZE: usize = 60;
+const MEMBER_ALIGN: u64 = 0x1000;
+
+fn be(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |acc, b| (acc << 8) | *b as u64)
+}
+
+fn le(bytes: &[u8]) -> u64 {
+    bytes.iter().rev().fold(0, |acc, b| (acc << 8) | *b as u64)
+}
+
+// Header offsets of members every entry of archive symbol index points to
+fn symbol_index(data: &[u8]) -> Vec<u64> {
+    let parse = || -> Option<Vec<u64>> {
+        let header = data.get(8..8 + HEADER_SIZE)?;
+        let name = std::str::from_utf8(&header[0..16]).ok()?.trim_end();
+        let size: usize = std::str::from_utf8(&header[48..58])
+            .ok()?
+            .trim()
+            .parse()
+            .ok()?;
+        let mut body = data.get(8 + HEADER_SIZE..8 + HEADER_SIZE + size)?;
+
+        // BSD stores long names right before the data
+        let name = match name.strip_prefix("#1/") {
+            Some(len) => {
+                let len: usize = len.parse().ok()?;
+                let long = std::str::from_utf8(body.get(..len)?).ok()?;
+
+                body = &body[len..];
+                long.trim_end_matches('\0')
+            }
+            None => name,
+        };
+
+        match name {
+            "/" | "/SYM64/" => {
+                let word = if name == "/" { 4 } else { 8 };
+                let count = be(body.get(..word)?) as usize;
+
+                (0..count)
+                    .map(|i| Some(be(body.get(word * (i + 1)..word * (i + 2))?)))
+                    .collect()
+            }
+            n if n.starts_with("__.SYMDEF") => {
+                let size = le(body.get(..4)?) as usize;
+
+                body.get(4..4 + size)?
+                    .chunks_exact(8)
+                    .map(|e| Some(le(&e[4..8])))
+                    .collect()
+            }
+            _ => None,
+        }
+    };
+
+    parse().unwrap_or_default()
+}
+
+impl Archive {
+    pub fn new(raw_data: &'static [u8]) -> Option<Self> {
+        let archive = match ArchiveFile::parse(raw_data) {
+            Ok(a) => Some(a),
+            Err(e) => {
+                error!("Failed to parse archive {}", e);
+                None
+            }
+        }?;
+
+        let members: Vec<_> = archive.members().filter_map(|m| m.ok()).collect();
+        let index = symbol_index(raw_data);
+
+        let mut objects = Vec::new();
+        let mut base = 0;
+
+        for m in members.iter() {
+            let (offset, _) = m.file_range();
+            let data = m.data(raw_data).ok()?;
+
+            // Index points to member headers, member data follows the header
+            // (and the long name for BSD)
+            let symbols = index
+                .iter()
+                .filter(|s| {
+                    **s < offset
+                        && !members
+                            .iter()
+                            .any(|o| **s < o.file_range().0 && o.file_range().0 < offset)
+                })
+                .count();
+
+            let elf = match FileKind::parse(data) {
+                Ok(FileKind::Elf32) | Ok(FileKind::Elf64) => Elf::with_base(data, base),
+                _ => None,
+            };
+
+            let end = elf.as_ref().map(|e| e.end()).unwrap_or(base);
+
+            objects.push(Object {
+                name: String::from_utf8_lossy(m.name()).into_owned(),
+                symbols,
+                elf,
+                start: base,
+                end,
+            });
+
+            base = end.next_multiple_of(MEMBER_ALIGN);
+        }
+
+        if objects.iter().all(|o| o.elf.is_none()) {
+            error!("Archive has no ELF objects");
+            return None;
+        }
+
+        Some(Self { objects })
+    }
+
+    fn member(&self, addr: u64) -> Option<&Object> {
+        self.objects
+            .iter()
+            .find(|o| o.start <= addr && addr < o.end)
+    }
+
+    fn object(&self, addr: u64) -> Option<&Elf> {
+        self.member(addr)?.elf.as_ref()
+    }
+
+    // Names of different members may clash, so they are told by member
+    fn functions(o: &Object) -> Vec<Function> {
+        match &o.elf {
+            Some(elf) => elf
+                .function_names()
+                .into_iter()
+                .map(|f| Self::in_member(&o.name, f))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn in_member(member: &str, f: Function) -> Function {
+        let new = Function::new(format!("{}: {}", member, f.name()), f.addr(), f.size());
+
+        match f.tag() {
+            Some(tag) => new.tagged(tag),
+            None => new,
+        }
+    }
+}
+
+impl Binary for Archive {
+    fn arch(&self) -> Arch {
+        self.mode().arch
+    }
+
+    fn mode(&self) -> Mode {
+        match self.objects.iter().find_map(|o| o.elf.as_ref()) {
+            Some(elf) => elf.mode(),
+            None => Mode::new(Arch::Unknown),
+        }
+    }
+
+    fn function_by_addr(&self, addr: u64) -> Option<Function> {
+        let o = self.member(addr)?;
+        let f = o.elf.as_ref()?.function_by_addr(addr)?;
+
+        Some(Self::in_member(&o.name, f))
+    }
+
+    fn function_names(&self) -> Vec<Function> {
+        self.objects.iter().flat_map(Self::functions).collect()
+    }
+
+    fn sections(&self) -> Vec<Section> {
+        self.objects
+            .iter()
+            .filter_map(|o| o.elf.as_ref())
+            .flat_map(|elf| elf.sections())
+            .collect()
+    }
+
+    fn func_code(&self, f: &Function) -> (&[u8], u64) {
+        match self.object(f.addr()) {
+            Some(elf) => elf.func_code(f),
+            None => (&[], f.addr()),
+        }
+    }
+
+    fn reloc_at(&self, addr: u64, size: usize) -> Option<Reloc> {
+        self.object(addr)?.reloc_at(addr, size)
+    }
+
+    fn symbol_at(&self, addr: u64) -> Option<(String, u64)> {
+        let o = self.member(addr)?;
+        let (name, off) = o.elf.as_ref()?.symbol_at(addr)?;
+
+        Some((format!("{}: {}", o.name, name), off))
+    }
+
+    fn bytes_at(&self, addr: u64) -> Option<&[u8]> {
+        self.object(addr)?.bytes_at(addr)
+    }
+
+    fn code_map(&self, start: u64, end: u64) -> Vec<(u64, CodeKind)> {
+        match self.object(start) {
+            Some(elf) => elf.code_map(start, end),
+            None => Vec::new(),
+        }
+    }
+
+    fn members(&self) -> Vec<Member> {
+        self.objects
+            .iter()
+            .map(|o| Member {
+                name: o.name.clone(),
+                symbols: o.symbols,
+                functions: Self::functions(o),
+            })
+            .collect()
+    }
+
+    fn function_debug_info(&self, f: &Function) -> Option<FunctionDebugInfo> {
+        self.object(f.addr())?.function_debug_info(f)
+    }
+}
@@ -0,0 +1,90 @@
+use crate::binary::Mode;
+use crate::decoder::Syntax;
+
+pub struct Args {
+    pub file: String,
+    pub raw: bool,
+    pub mode: Option<Mode>, // Decoder mode given by user
+    pub base: u64,
+    pub debug_dir: Option<String>,
+    pub syntax: Syntax, // x86 syntax to start with
+}
+
+pub fn usage(name: &str) {
+    error!(
+        "usage: {} [--raw [--base <addr>]] [--arch <mode>] [--syntax intel|att|masm] \
+         [--debug-dir <dir>] <file>",
+        name
+    );
+    error!(
+        "modes: x86 x86_64 arm armbe thumb thumbbe aarch64 aarch64_be riscv32[c] riscv64[c] \
+         mips[el] mips64[el] mips32r6[el] micromips[el] ppc[le] ppc64[le] s390x sparc sparcv9 \
+         m68k bpf[eb]"
+    );
+}
+
+fn parse_num(s: &str) -> Option<u64> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+impl Args {
+    pub fn parse(args: &[String]) -> Option<Self> {
+        let mut file = None;
+        let mut raw = false;
+        let mut mode = None;
+        let mut base = 0;
+        let mut debug_dir = None;
+        let mut syntax = Syntax::Intel;
+
+        let mut iter = args.iter().skip(1);
+
+        while let Some(a) = iter.next() {
+            match a.as_str() {
+                "--raw" => raw = true,
+                "--arch" => {
+                    let name = iter.next()?;
+
+                    mode = match Mode::parse(name) {
+                        Some(m) => Some(m),
+                        None => {
+                            error!("Unknown arch {}", name);
+                            return None;
+                        }
+                    }
+                }
+                "--syntax" => {
+                    let name = iter.next()?;
+
+                    syntax = match Syntax::parse(name) {
+                        Some(s) => s,
+                        None => {
+                            error!("Unknown syntax {}", name);
+                            return None;
+                        }
+                    }
+                }
+                "--base" => base = parse_num(iter.next()?)?,
+                "--debug-dir" => debug_dir = Some(iter.next()?.clone()),
+                _ if file.is_none() && !a.starts_with("--") => file = Some(a.clone()),
+                _ => return None,
+            }
+        }
+
+        if raw && mode.is_none() {
+            error!("--raw needs --arch");
+            return None;
+        }
+
+        Some(Self {
+            file: file?,
+            raw,
+            mode,
+            base,
+            debug_dir,
+            syntax,
+        })
+    }
+}
@@ -0,0 +1,466 @@
+use crate::archive::Archive;
+use crate::debug_file::DebugSearch;
+use crate::dwarf::FunctionDebugInfo;
+use crate::elf::Elf;
+use crate::macho::MachO;
+use crate::pe::Pe;
+use object::{Architecture, FileKind, Object, ObjectSection, SymbolMap, SymbolMapName};
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum Arch {
+    X86,
+    X86_64,
+    Arm64,
+    Arm,
+    Riscv,
+    Mips,
+    Ppc,
+    S390x,
+    Sparc,
+    M68k,
+    Bpf,
+    // Machine there is no decoder for
+    Unknown,
+}
+
+// ISA extensions decoder has to be told about
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum Ext {
+    None,
+    RiscvC,
+    Mips2,
+    Mips3,
+    Mips32R6,
+    MicroMips,
+}
+
+// What bytes between two mapping symbols are
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum CodeKind {
+    Arm,
+    Thumb,
+    Data,
+}
+
+// Everything needed to pick the right decoder
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub struct Mode {
+    pub arch: Arch,
+    pub bits64: bool,
+    pub big_endian: bool,
+    // ARM BE8 images keep code little endian while data is big endian
+    pub be8: bool,
+    pub thumb: bool,
+    pub ext: Ext,
+}
+
+#[derive(Clone)]
+pub struct Function {
+    name: String,
+    addr: u64,
+    size: usize,
+    tag: Option<&'static str>, // Where function came from, e.g. symbol table
+}
+
+pub struct Section {
+    pub name: String,
+    pub addr: u64,
+    pub size: u64,
+}
+
+// Function of shared library called through PLT stub or GOT slot
+#[derive(Clone)]
+pub struct Import {
+    pub name: String,
+    // Stub and section it is in, there is none for calls through GOT
+    pub stub: Option<Function>,
+    pub section: String,
+    pub got: u64,
+    // Table slot is in, GOT or IAT
+    pub table: &'static str,
+    pub reloc: &'static str,
+    pub version: Option<String>,
+    pub library: Option<String>,
+    pub needed: Vec<String>,
+    // Definition in the file itself, which slot points to unless preempted
+    pub local: Option<u64>,
+}
+
+// Relocation of instruction in relocatable object
+#[derive(Clone)]
+pub struct Reloc {
+    pub name: String,
+    pub kind: String,
+    pub target: Option<u64>,
+}
+
+// Object file of static archive
+pub struct Member {
+    pub name: String,
+    pub symbols: usize,
+    pub functions: Vec<Function>,
+}
+
+impl Function {
+    pub fn new(name: String, addr: u64, size: usize) -> Self {
+        Self {
+            name,
+            addr,
+            size,
+            tag: None,
+        }
+    }
+
+    pub fn tagged(self, tag: &'static str) -> Self {
+        Self {
+            tag: Some(tag),
+            ..self
+        }
+    }
+
+    pub fn resized(self, size: usize) -> Self {
+        Self { size, ..self }
+    }
+
+    pub fn tag(&self) -> Option<&'static str> {
+        self.tag
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn addr(&self) -> u64 {
+        self.addr
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl Mode {
+    pub fn new(arch: Arch) -> Self {
+        Self {
+            arch,
+            bits64: matches!(
+                arch,
+                Arch::X86_64 | Arch::Arm64 | Arch::Riscv | Arch::S390x | Arch::Bpf
+            ),
+            big_endian: matches!(arch, Arch::Ppc | Arch::S390x | Arch::Sparc | Arch::M68k),
+            be8: false,
+            thumb: false,
+            ext: Ext::None,
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        let m = |arch, bits64, big_endian, thumb, ext| Self {
+            arch,
+            bits64,
+            big_endian,
+            be8: false,
+            thumb,
+            ext,
+        };
+
+        match name {
+            "x86" | "i386" => Some(m(Arch::X86, false, false, false, Ext::None)),
+            "x86_64" | "amd64" => Some(m(Arch::X86_64, true, false, false, Ext::None)),
+            "arm" => Some(m(Arch::Arm, false, false, false, Ext::None)),
+            "armbe" => Some(m(Arch::Arm, false, true, false, Ext::None)),
+            "thumb" => Some(m(Arch::Arm, false, false, true, Ext::None)),
+            "thumbbe" => Some(m(Arch::Arm, false, true, true, Ext::None)),
+            "aarch64" | "arm64" => Some(m(Arch::Arm64, true, false, false, Ext::None)),
+            "aarch64_be" => Some(m(Arch::Arm64, true, true, false, Ext::None)),
+            "riscv32" => Some(m(Arch::Riscv, false, false, false, Ext::None)),
+            "riscv32c" => Some(m(Arch::Riscv, false, false, false, Ext::RiscvC)),
+            "riscv64" | "riscv" => Some(m(Arch::Riscv, true, false, false, Ext::None)),
+            "riscv64c" => Some(m(Arch::Riscv, true, false, false, Ext::RiscvC)),
+            "mips" => Some(m(Arch::Mips, false, true, false, Ext::None)),
+            "mipsel" => Some(m(Arch::Mips, false, false, false, Ext::None)),
+            "mips64" => Some(m(Arch::Mips, true, true, false, Ext::None)),
+            "mips64el" => Some(m(Arch::Mips, true, false, false, Ext::None)),
+            "mips32r6" => Some(m(Arch::Mips, false, true, false, Ext::Mips32R6)),
+            "mips32r6el" => Some(m(Arch::Mips, false, false, false, Ext::Mips32R6)),
+            "micromips" => Some(m(Arch::Mips, false, true, false, Ext::MicroMips)),
+            "micromipsel" => Some(m(Arch::Mips, false, false, false, Ext::MicroMips)),
+            "ppc" => Some(m(Arch::Ppc, false, true, false, Ext::None)),
+            "ppcle" => Some(m(Arch::Ppc, false, false, false, Ext::None)),
+            "ppc64" => Some(m(Arch::Ppc, true, true, false, Ext::None)),
+            "ppc64le" => Some(m(Arch::Ppc, true, false, false, Ext::None)),
+            "s390x" => Some(m(Arch::S390x, true, true, false, Ext::None)),
+            "sparc" => Some(m(Arch::Sparc, false, true, false, Ext::None)),
+            "sparcv9" => Some(m(Arch::Sparc, true, true, false, Ext::None)),
+            "m68k" => Some(m(Arch::M68k, false, true, false, Ext::None)),
+            "bpf" | "bpfel" => Some(m(Arch::Bpf, true, false, false, Ext::None)),
+            "bpfeb" => Some(m(Arch::Bpf, true, true, false, Ext::None)),
+            _ => None,
+        }
+    }
+}
+
+/// Format-neutral view of an executable: whatever the container is, frames
+/// only need to know which functions exist and where their code lives.
+pub trait Binary {
+    fn arch(&self) -> Arch;
+    fn function_by_addr(&self, addr: u64) -> Option<Function>;
+    fn function_names(&self) -> Vec<Function>;
+    fn sections(&self) -> Vec<Section>;
+
+    // Code of the function and the address of its first byte
+    fn func_code(&self, f: &Function) -> (&[u8], u64);
+
+    // Defines a new function at addr, if format allows that
+    fn add_function(&mut self, _addr: u64) -> Option<Function> {
+        None
+    }
+
+    // Whether functions were guessed, so following calls finds more of them
+    fn stripped(&self) -> bool {
+        false
+    }
+
+    fn mode(&self) -> Mode {
+        Mode::new(self.arch())
+    }
+
+    fn function_debug_info(&self, _f: &Function) -> Option<FunctionDebugInfo> {
+        None
+    }
+
+    // Import whose stub starts at addr
+    fn import_by_addr(&self, _addr: u64) -> Option<Import> {
+        None
+    }
+
+    // Import whose address is stored in GOT slot at addr
+    fn import_by_got(&self, _addr: u64) -> Option<Import> {
+        None
+    }
+
+    // Changes of code kind in [start, end), the first one is in effect at start
+    fn code_map(&self, _start: u64, _end: u64) -> Vec<(u64, CodeKind)> {
+        Vec::new()
+    }
+
+    // Relocation patching bytes of instruction at addr
+    fn reloc_at(&self, _addr: u64, _size: usize) -> Option<Reloc> {
+        None
+    }
+
+    // Symbol containing addr, data objects included, and offset into it
+    fn symbol_at(&self, _addr: u64) -> Option<(String, u64)> {
+        None
+    }
+
+    // Contents of the image from addr to the end of its section
+    fn bytes_at(&self, _addr: u64) -> Option<&[u8]> {
+        None
+    }
+
+    // Picks up symbols and DWARF from separate debug file, if there is one
+    fn load_debug_file(&mut self, _search: &DebugSearch) {}
+
+    // Objects the binary consists of, empty if it is not an archive
+    fn members(&self) -> Vec<Member> {
+        Vec::new()
+    }
+}
+
+// If format does not give sizes for functions, simply look up next function
+// start or the end of the containing section
+pub fn fixup_sizes(funcs: &mut [Function], sections: &[Section]) {
+    funcs.sort_by_key(|f| f.addr);
+
+    for i in 0..funcs.len() {
+        if funcs[i].size != 0 {
+            continue;
+        }
+
+        let addr = funcs[i].addr;
+        let section_end = sections
+            .iter()
+            .find(|s| s.addr <= addr && addr < s.addr + s.size)
+            .map(|s| s.addr + s.size);
+
+        let next = funcs[i + 1..].iter().map(|f| f.addr).find(|a| *a > addr);
+
+        let end = match (next, section_end) {
+            (Some(n), Some(s)) => u64::min(n, s),
+            (Some(n), None) => n,
+            (None, Some(s)) => s,
+            (None, None) => addr,
+        };
+
+        funcs[i].size = (end - addr) as usize;
+    }
+}
+
+pub fn object_arch(arch: Architecture) -> Arch {
+    match arch {
+        Architecture::X86_64 => Arch::X86_64,
+        Architecture::I386 => Arch::X86,
+        Architecture::Aarch64 => Arch::Arm64,
+        Architecture::Arm => Arch::Arm,
+        Architecture::Riscv32 | Architecture::Riscv64 => Arch::Riscv,
+        Architecture::Mips | Architecture::Mips64 => Arch::Mips,
+        Architecture::PowerPc | Architecture::PowerPc64 => Arch::Ppc,
+        Architecture::S390x => Arch::S390x,
+        Architecture::Sparc64 => Arch::Sparc,
+        _ => Arch::Unknown,
+    }
+}
+
+pub fn object_sections(obj: &object::File<'static>) -> Vec<Section> {
+    obj.sections()
+        .map(|s| Section {
+            name: s.name().unwrap_or("").to_owned(),
+            addr: s.address(),
+            size: s.size(),
+        })
+        .collect()
+}
+
+// Symbol at or before addr, object formats other than ELF give no sizes, so
+// there is no telling whether addr is still inside of it
+pub fn object_symbol(map: &SymbolMap<SymbolMapName<'static>>, addr: u64) -> Option<(String, u64)> {
+    let sym = map.get(addr)?;
+
+    Some((sym.name().to_owned(), addr - sym.address()))
+}
+
+// Data of the section containing addr, starting at addr
+pub fn object_bytes(obj: &object::File<'static>, addr: u64) -> Option<&'static [u8]> {
+    let section = obj
+        .sections()
+        .find(|s| s.address() <= addr && addr < s.address() + s.size())?;
+    let data = section.data().ok()?;
+
+    data.get((addr - section.address()) as usize..)
+}
+
+// Code of size bytes at addr. Raw data of a section may be shorter than its
+// virtual size, so the slice is cut to whatever is present in the file
+pub fn object_code(obj: &object::File<'static>, addr: u64, size: usize) -> &'static [u8] {
+    let section = obj
+        .sections()
+        .find(|s| s.address() <= addr && addr < s.address() + s.size());
+
+    match section.as_ref().map(|s| (s.address(), s.data())) {
+        Some((s_addr, Ok(d))) => {
+            let start = (addr - s_addr) as usize;
+            let end = usize::min(start + size, d.len());
+
+            if start < end {
+                &d[start..end]
+            } else {
+                &d[0..0]
+            }
+        }
+        _ => &[],
+    }
+}
+
+// Binary decoded in mode user asked for instead of the one in its header
+pub struct ModeOverride {
+    bin: Box<dyn Binary>,
+    mode: Mode,
+}
+
+impl ModeOverride {
+    pub fn new(bin: Box<dyn Binary>, mode: Mode) -> Self {
+        Self { bin, mode }
+    }
+}
+
+impl Binary for ModeOverride {
+    fn arch(&self) -> Arch {
+        self.mode.arch
+    }
+
+    fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    fn function_by_addr(&self, addr: u64) -> Option<Function> {
+        self.bin.function_by_addr(addr)
+    }
+
+    fn function_names(&self) -> Vec<Function> {
+        self.bin.function_names()
+    }
+
+    fn sections(&self) -> Vec<Section> {
+        self.bin.sections()
+    }
+
+    fn func_code(&self, f: &Function) -> (&[u8], u64) {
+        self.bin.func_code(f)
+    }
+
+    fn add_function(&mut self, addr: u64) -> Option<Function> {
+        self.bin.add_function(addr)
+    }
+
+    fn stripped(&self) -> bool {
+        self.bin.stripped()
+    }
+
+    fn function_debug_info(&self, f: &Function) -> Option<FunctionDebugInfo> {
+        self.bin.function_debug_info(f)
+    }
+
+    fn import_by_addr(&self, addr: u64) -> Option<Import> {
+        self.bin.import_by_addr(addr)
+    }
+
+    fn import_by_got(&self, addr: u64) -> Option<Import> {
+        self.bin.import_by_got(addr)
+    }
+
+    fn reloc_at(&self, addr: u64, size: usize) -> Option<Reloc> {
+        self.bin.reloc_at(addr, size)
+    }
+
+    fn symbol_at(&self, addr: u64) -> Option<(String, u64)> {
+        self.bin.symbol_at(addr)
+    }
+
+    fn bytes_at(&self, addr: u64) -> Option<&[u8]> {
+        self.bin.bytes_at(addr)
+    }
+
+    fn code_map(&self, start: u64, end: u64) -> Vec<(u64, CodeKind)> {
+        self.bin.code_map(start, end)
+    }
+
+    fn load_debug_file(&mut self, search: &DebugSearch) {
+        self.bin.load_debug_file(search)
+    }
+
+    fn members(&self) -> Vec<Member> {
+        self.bin.members()
+    }
+}
+
+pub fn load(data: &'static [u8]) -> Option<Box<dyn Binary>> {
+    let kind = match FileKind::parse(data) {
+        Ok(k) => Some(k),
+        Err(e) => {
+            error!("Unknown file format: {}", e);
+            None
+        }
+    }?;
+
+    match kind {
+        FileKind::Elf32 | FileKind::Elf64 => Some(Box::new(Elf::new(data)?)),
+        FileKind::Pe32 | FileKind::Pe64 => Some(Box::new(Pe::new(data)?)),
+        FileKind::MachO32 | FileKind::MachO64 => Some(Box::new(MachO::new(data)?)),
+        FileKind::Archive => Some(Box::new(Archive::new(data)?)),
+        _ => {
+            error!("Unsupported file format {:?}", kind);
+            None
+        }
+    }
+}
@@ -0,0 +1,508 @@
+use crate::analysis::BranchInst;
+use crate::binary::Function;
+use crate::decoder::Inst;
+use elf::endian::AnyEndian;
+use elf::ElfBytes;
+
+// eBPF is not known to capstone we use, but its encoding is simple: 8 byte
+// instructions, except for 16 byte load of 64-bit immediate
+
+// Helper functions by their number, as in enum bpf_func_id
+const HELPERS: [&str; 166] = [
+    "unspec",
+    "map_lookup_elem",
+    "map_update_elem",
+    "map_delete_elem",
+    "probe_read",
+    "ktime_get_ns",
+    "trace_printk",
+    "get_prandom_u32",
+    "get_smp_processor_id",
+    "skb_store_bytes",
+    "l3_csum_replace",
+    "l4_csum_replace",
+    "tail_call",
+    "clone_redirect",
+    "get_current_pid_tgid",
+    "get_current_uid_gid",
+    "get_current_comm",
+    "get_cgroup_classid",
+    "skb_vlan_push",
+    "skb_vlan_pop",
+    "skb_get_tunnel_key",
+    "skb_set_tunnel_key",
+    "perf_event_read",
+    "redirect",
+    "get_route_realm",
+    "perf_event_output",
+    "skb_load_bytes",
+    "get_stackid",
+    "csum_diff",
+    "skb_get_tunnel_opt",
+    "skb_set_tunnel_opt",
+    "skb_change_proto",
+    "skb_change_type",
+    "skb_under_cgroup",
+    "get_hash_recalc",
+    "get_current_task",
+    "probe_write_user",
+    "current_task_under_cgroup",
+    "skb_change_tail",
+    "skb_pull_data",
+    "csum_update",
+    "set_hash_invalid",
+    "get_numa_node_id",
+    "skb_change_head",
+    "xdp_adjust_head",
+    "probe_read_str",
+    "get_socket_cookie",
+    "get_socket_uid",
+    "set_hash",
+    "setsockopt",
+    "skb_adjust_room",
+    "redirect_map",
+    "sk_redirect_map",
+    "sock_map_update",
+    "xdp_adjust_meta",
+    "perf_event_read_value",
+    "perf_prog_read_value",
+    "getsockopt",
+    "override_return",
+    "sock_ops_cb_flags_set",
+    "msg_redirect_map",
+    "msg_apply_bytes",
+    "msg_cork_bytes",
+    "msg_pull_data",
+    "bind",
+    "xdp_adjust_tail",
+    "skb_get_xfrm_state",
+    "get_stack",
+    "skb_load_bytes_relative",
+    "fib_lookup",
+    "sock_hash_update",
+    "msg_redirect_hash",
+    "sk_redirect_hash",
+    "lwt_push_encap",
+    "lwt_seg6_store_bytes",
+    "lwt_seg6_adjust_srh",
+    "lwt_seg6_action",
+    "rc_repeat",
+    "rc_keydown",
+    "skb_cgroup_id",
+    "get_current_cgroup_id",
+    "get_local_storage",
+    "sk_select_reuseport",
+    "skb_ancestor_cgroup_id",
+    "sk_lookup_tcp",
+    "sk_lookup_udp",
+    "sk_release",
+    "map_push_elem",
+    "map_pop_elem",
+    "map_peek_elem",
+    "msg_push_data",
+    "msg_pop_data",
+    "rc_pointer_rel",
+    "spin_lock",
+    "spin_unlock",
+    "sk_fullsock",
+    "tcp_sock",
+    "skb_ecn_set_ce",
+    "get_listener_sock",
+    "skc_lookup_tcp",
+    "tcp_check_syncookie",
+    "sysctl_get_name",
+    "sysctl_get_current_value",
+    "sysctl_get_new_value",
+    "sysctl_set_new_value",
+    "strtol",
+    "strtoul",
+    "sk_storage_get",
+    "sk_storage_delete",
+    "send_signal",
+    "tcp_gen_syncookie",
+    "skb_output",
+    "probe_read_user",
+    "probe_read_kernel",
+    "probe_read_user_str",
+    "probe_read_kernel_str",
+    "tcp_send_ack",
+    "send_signal_thread",
+    "jiffies64",
+    "read_branch_records",
+    "get_ns_current_pid_tgid",
+    "xdp_output",
+    "get_netns_cookie",
+    "get_current_ancestor_cgroup_id",
+    "sk_assign",
+    "ktime_get_boot_ns",
+    "seq_printf",
+    "seq_write",
+    "sk_cgroup_id",
+    "sk_ancestor_cgroup_id",
+    "ringbuf_output",
+    "ringbuf_reserve",
+    "ringbuf_submit",
+    "ringbuf_discard",
+    "ringbuf_query",
+    "csum_level",
+    "skc_to_tcp6_sock",
+    "skc_to_tcp_sock",
+    "skc_to_tcp_timewait_sock",
+    "skc_to_tcp_request_sock",
+    "skc_to_udp6_sock",
+    "get_task_stack",
+    "load_hdr_opt",
+    "store_hdr_opt",
+    "reserve_hdr_opt",
+    "inode_storage_get",
+    "inode_storage_delete",
+    "d_path",
+    "copy_from_user",
+    "snprintf_btf",
+    "seq_printf_btf",
+    "skb_cgroup_classid",
+    "redirect_neigh",
+    "per_cpu_ptr",
+    "this_cpu_ptr",
+    "redirect_peer",
+    "task_storage_get",
+    "task_storage_delete",
+    "get_current_task_btf",
+    "bprm_opts_set",
+    "ktime_get_coarse_ns",
+    "ima_inode_hash",
+    "sock_from_file",
+    "check_mtu",
+    "for_each_map_elem",
+    "snprintf",
+];
+
+const ALU_OPS: [&str; 14] = [
+    "add", "sub", "mul", "div", "or", "and", "lsh", "rsh", "neg", "mod", "xor", "mov", "arsh",
+    "end",
+];
+
+const JMP_OPS: [&str; 14] = [
+    "ja", "jeq", "jgt", "jge", "jset", "jne", "jsgt", "jsge", "call", "exit", "jlt", "jle", "jslt",
+    "jsle",
+];
+
+fn helper(id: i32) -> String {
+    match HELPERS.get(id as usize) {
+        Some(name) if id > 0 => format!("bpf_{}", name),
+        _ => format!("helper #{}", id),
+    }
+}
+
+fn hex(x: i64) -> String {
+    if x < 0 {
+        format!("-0x{:x}", -x)
+    } else {
+        format!("0x{:x}", x)
+    }
+}
+
+fn size(op: u8) -> &'static str {
+    match op & 0x18 {
+        0x00 => "w",
+        0x08 => "h",
+        0x10 => "b",
+        _ => "dw",
+    }
+}
+
+fn mem(reg: u8, off: i16) -> String {
+    match off {
+        0 => format!("[r{}]", reg),
+        o if o < 0 => format!("[r{}-0x{:x}]", reg, -(o as i32)),
+        o => format!("[r{}+0x{:x}]", reg, o),
+    }
+}
+
+// One instruction at the start of bytes, None if it is truncated or opcode is
+// unknown
+pub fn decode(bytes: &[u8], addr: u64, big_endian: bool) -> Option<Inst> {
+    let b = bytes.get(0..8)?;
+    let op = b[0];
+    let (dst, src) = if big_endian {
+        (b[1] >> 4, b[1] & 0xf)
+    } else {
+        (b[1] & 0xf, b[1] >> 4)
+    };
+
+    let (off, imm) = if big_endian {
+        (
+            i16::from_be_bytes([b[2], b[3]]),
+            i32::from_be_bytes([b[4], b[5], b[6], b[7]]),
+        )
+    } else {
+        (
+            i16::from_le_bytes([b[2], b[3]]),
+            i32::from_le_bytes([b[4], b[5], b[6], b[7]]),
+        )
+    };
+
+    let mut len = 8;
+    let mut branch = None;
+    let target = |n: i64| addr.wrapping_add(((n + 1) * 8) as u64);
+    let operand = if op & 0x08 != 0 {
+        format!("r{}", src)
+    } else {
+        hex(imm as i64)
+    };
+
+    let (mnemonic, op_str) = match op & 0x07 {
+        // alu32, alu64
+        class @ (0x04 | 0x07) => {
+            let code = (op >> 4) as usize;
+            let suffix = if class == 0x04 { "32" } else { "" };
+
+            match *ALU_OPS.get(code)? {
+                "neg" => (format!("neg{}", suffix), format!("r{}", dst)),
+                "end" => {
+                    let order = match (class, op & 0x08) {
+                        (0x07, _) => "bswap",
+                        (_, 0) => "le",
+                        _ => "be",
+                    };
+
+                    (format!("{}{}", order, imm), format!("r{}", dst))
+                }
+                name => (
+                    format!("{}{}", name, suffix),
+                    format!("r{}, {}", dst, operand),
+                ),
+            }
+        }
+        // jmp, jmp32
+        class @ (0x05 | 0x06) => {
+            let code = (op >> 4) as usize;
+            let suffix = if class == 0x06 { "32" } else { "" };
+
+            match *JMP_OPS.get(code)? {
+                "exit" => {
+                    branch = Some(BranchInst::Return);
+                    ("exit".to_owned(), String::new())
+                }
+                "call" => match src {
+                    // Call of another function of the object
+                    1 => {
+                        branch = Some(BranchInst::Call(target(imm as i64)));
+                        ("call".to_owned(), format!("0x{:x}", target(imm as i64)))
+                    }
+                    2 => ("call".to_owned(), format!("kfunc #{}", imm)),
+                    _ => ("call".to_owned(), helper(imm)),
+                },
+                "ja" => {
+                    // 32-bit variant keeps offset in immediate
+                    let to = if class == 0x06 {
+                        target(imm as i64)
+                    } else {
+                        target(off as i64)
+                    };
+
+                    branch = Some(BranchInst::Jump(to));
+                    (format!("ja{}", suffix), format!("0x{:x}", to))
+                }
+                name => {
+                    branch = Some(BranchInst::Jump(target(off as i64)));
+                    (
+                        format!("{}{}", name, suffix),
+                        format!("r{}, {}, 0x{:x}", dst, operand, target(off as i64)),
+                    )
+                }
+            }
+        }
+        // ld
+        0x00 => match op {
+            0x18 => {
+                let hi = decode_imm(bytes.get(8..16)?, big_endian);
+
+                len = 16;
+                (
+                    "lddw".to_owned(),
+                    format!("r{}, 0x{:x}", dst, (imm as u32 as u64) | (hi as u64) << 32),
+                )
+            }
+            _ if op & 0xe0 == 0x20 => (format!("ldabs{}", size(op)), hex(imm as i64)),
+            _ if op & 0xe0 == 0x40 => (
+                format!("ldind{}", size(op)),
+                format!("r{}, {}", src, hex(imm as i64)),
+            ),
+            _ => return None,
+        },
+        // ldx
+        0x01 => match op & 0xe0 {
+            0x60 => (
+                format!("ldx{}", size(op)),
+                format!("r{}, {}", dst, mem(src, off)),
+            ),
+            0x80 => (
+                format!("ldxs{}", size(op)),
+                format!("r{}, {}", dst, mem(src, off)),
+            ),
+            _ => return None,
+        },
+        // st
+        0x02 => match op & 0xe0 {
+            0x60 => (
+                format!("st{}", size(op)),
+                format!("{}, {}", mem(dst, off), hex(imm as i64)),
+            ),
+            _ => return None,
+        },
+        // stx
+        _ => match op & 0xe0 {
+            0x60 => (
+                format!("stx{}", size(op)),
+                format!("{}, r{}", mem(dst, off), src),
+            ),
+            0xc0 => {
+                let name = match imm {
+                    0x00 => "xadd".to_owned(),
+                    0xe1 => "xchg".to_owned(),
+                    0xf1 => "cmpxchg".to_owned(),
+                    i => {
+                        let fetch = if i & 0x01 != 0 { "fetch_" } else { "" };
+
+                        format!("atomic_{}{}", fetch, ALU_OPS.get((i >> 4) as usize)?)
+                    }
+                };
+
+                (
+                    format!("{}{}", name, size(op)),
+                    format!("{}, r{}", mem(dst, off), src),
+                )
+            }
+            _ => return None,
+        },
+    };
+
+    Some(Inst {
+        addr,
+        bytes: bytes[..len].to_vec(),
+        mnemonic,
+        op_str,
+        branch,
+        target: None,
+        cases: Vec::new(),
+        delay_slot: false,
+    })
+}
+
+fn decode_imm(b: &[u8], big_endian: bool) -> u32 {
+    if big_endian {
+        u32::from_be_bytes([b[4], b[5], b[6], b[7]])
+    } else {
+        u32::from_le_bytes([b[4], b[5], b[6], b[7]])
+    }
+}
+
+// Every section with code except .text is a program, which is loaded and
+// attached separately. .text holds functions programs call
+pub fn programs(
+    data: &ElfBytes<'static, AnyEndian>,
+    section_addr: &[u64],
+) -> Vec<(u64, (Function, u16))> {
+    let (sections, strtab) = match data.section_headers_with_strtab() {
+        Ok((Some(s), Some(t))) => (s, t),
+        _ => return Vec::new(),
+    };
+
+    sections
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| s.sh_flags & elf::abi::SHF_EXECINSTR as u64 != 0 && s.sh_size != 0)
+        .filter_map(|(i, s)| {
+            let name = strtab.get(s.sh_name as usize).ok()?;
+            let addr = *section_addr.get(i)?;
+
+            if name == ".text" {
+                return None;
+            }
+
+            Some((
+                addr,
+                (
+                    Function::new(name.to_owned(), addr, s.sh_size as usize).tagged("prog"),
+                    i as u16,
+                ),
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(bytes: &[u8], addr: u64) -> (String, String) {
+        let inst = decode(bytes, addr, false).unwrap();
+
+        (inst.mnemonic, inst.op_str)
+    }
+
+    #[test]
+    fn alu_and_memory() {
+        let cases: [(&[u8], &str, &str); 7] = [
+            (&[0xbf, 0x16, 0, 0, 0, 0, 0, 0], "mov", "r6, r1"),
+            (
+                &[0x07, 0x02, 0, 0, 0xfc, 0xff, 0xff, 0xff],
+                "add",
+                "r2, -0x4",
+            ),
+            (&[0xbc, 0x61, 0, 0, 0, 0, 0, 0], "mov32", "r1, r6"),
+            (&[0xdc, 0x01, 0, 0, 0x20, 0, 0, 0], "be32", "r1"),
+            (
+                &[0x63, 0x6a, 0xfc, 0xff, 0, 0, 0, 0],
+                "stxw",
+                "[r10-0x4], r6",
+            ),
+            (&[0x79, 0x11, 0x68, 0, 0, 0, 0, 0], "ldxdw", "r1, [r1+0x68]"),
+            (&[0xdb, 0x60, 0, 0, 0, 0, 0, 0], "xadddw", "[r0], r6"),
+        ];
+
+        for (bytes, mnemonic, op_str) in cases {
+            assert_eq!(text(bytes, 0), (mnemonic.to_owned(), op_str.to_owned()));
+        }
+    }
+
+    #[test]
+    fn wide_immediate() {
+        let bytes = [
+            0x18, 0x01, 0, 0, 0x78, 0x56, 0x34, 0x12, 0, 0, 0, 0, 0x01, 0, 0, 0,
+        ];
+        let inst = decode(&bytes, 0x20, false).unwrap();
+
+        assert_eq!(inst.bytes.len(), 16);
+        assert_eq!(inst.op_str, "r1, 0x112345678");
+        assert!(decode(&bytes[..8], 0x20, false).is_none());
+    }
+
+    #[test]
+    fn branches() {
+        // if r0 == 0 goto +3
+        let inst = decode(&[0x15, 0, 3, 0, 0, 0, 0, 0], 0x38, false).unwrap();
+        assert_eq!(inst.op_str, "r0, 0x0, 0x58");
+        assert!(matches!(inst.branch, Some(BranchInst::Jump(0x58))));
+
+        // Call of local function is relative, helpers are named
+        let inst = decode(&[0x85, 0x10, 0, 0, 0xff, 0xff, 0xff, 0xff], 0x58, false).unwrap();
+        assert!(matches!(inst.branch, Some(BranchInst::Call(0x58))));
+        assert_eq!(
+            text(&[0x85, 0, 0, 0, 1, 0, 0, 0], 0).1,
+            "bpf_map_lookup_elem"
+        );
+
+        let inst = decode(&[0x95, 0, 0, 0, 0, 0, 0, 0], 0, false).unwrap();
+        assert!(matches!(inst.branch, Some(BranchInst::Return)));
+    }
+
+    #[test]
+    fn big_endian() {
+        // Registers swap nibbles, fields are big endian
+        let inst = decode(&[0x07, 0x20, 0, 0, 0xff, 0xff, 0xff, 0xfc], 0, true).unwrap();
+
+        assert_eq!(inst.op_str, "r2, -0x4");
+    }
+}
@@ -0,0 +1,395 @@
+use crate::analysis::{self, BranchInst};
+use crate::binary::{Arch, Binary, Mode};
+use crate::decoder::Inst;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    // Conditional jump taken
+    True,
+    // Conditional jump falling through
+    False,
+    Unconditional,
+}
+
+// Instructions [start, end) of listing, entered only at start and left only
+// at end
+#[derive(Clone)]
+pub struct Block {
+    pub start: usize,
+    pub end: usize,
+    pub succs: Vec<(usize, EdgeKind)>,
+    pub preds: Vec<usize>,
+}
+
+// Decoders print condition into mnemonic, so jumps that always go are told by
+// name. Jumps through register have no target and are recognized here too
+fn always(mode: Mode, mnemonic: &str) -> bool {
+    let m = mnemonic.trim_start_matches("notrack ");
+
+    match mode.arch {
+        Arch::X86 | Arch::X86_64 => {
+            matches!(
+                m,
+                "jmp" | "jmpq" | "jmpl" | "jmpw" | "ljmp" | "ljmpl" | "ljmpq"
+            )
+        }
+        Arch::Arm64 => matches!(m, "b" | "br" | "braa" | "brab" | "braaz" | "brabz"),
+        Arch::Arm => matches!(m, "b" | "b.w" | "b.n" | "bx"),
+        Arch::Riscv => matches!(m, "j" | "jr" | "c.j" | "c.jr"),
+        Arch::Mips => matches!(m, "j" | "jr" | "b" | "bc" | "jrc" | "jr.hb"),
+        Arch::Ppc => matches!(m, "b" | "ba" | "bctr"),
+        Arch::S390x => matches!(m, "j" | "jg" | "br"),
+        Arch::Sparc => matches!(m, "b" | "ba" | "ba,a" | "jmp"),
+        Arch::M68k => m.starts_with("bra") || m == "jmp",
+        Arch::Bpf => m.starts_with("ja"),
+        Arch::Unknown => false,
+    }
+}
+
+// Whether control does not simply go on to next instruction
+fn transfers(mode: Mode, inst: &Inst) -> bool {
+    match inst.branch {
+        Some(BranchInst::Jump(_)) | Some(BranchInst::JumpVia(_)) | Some(BranchInst::Return) => true,
+        Some(BranchInst::Call(_)) | Some(BranchInst::CallVia(_)) => false,
+        None => !inst.cases.is_empty() || always(mode, &inst.mnemonic),
+    }
+}
+
+// Index of the first instruction after branch at i and its delay slot
+fn after(insts: &[Inst], i: usize) -> usize {
+    match insts.get(i + 1) {
+        Some(next) if next.delay_slot => i + 2,
+        _ => i + 1,
+    }
+}
+
+pub fn blocks(bin: &dyn Binary, insts: &[Inst]) -> Vec<Block> {
+    let mode = bin.mode();
+    let index: HashMap<u64, usize> = insts.iter().enumerate().map(|(i, x)| (x.addr, i)).collect();
+    let mut leader = vec![false; insts.len() + 1];
+
+    for (i, inst) in insts.iter().enumerate() {
+        if transfers(mode, inst) {
+            leader[after(insts, i)] = true;
+        }
+
+        if let Some(&j) = analysis::jump_target(bin, inst).and_then(|x| index.get(&x)) {
+            leader[j] = true;
+        }
+
+        for c in &inst.cases {
+            if let Some(&j) = index.get(c) {
+                leader[j] = true;
+            }
+        }
+    }
+
+    if insts.is_empty() {
+        return Vec::new();
+    }
+
+    leader[0] = true;
+
+    let mut blocks = Vec::new();
+    let mut block_of = vec![0; insts.len()];
+
+    for i in 0..insts.len() {
+        if leader[i] {
+            blocks.push(Block {
+                start: i,
+                end: i,
+                succs: Vec::new(),
+                preds: Vec::new(),
+            });
+        }
+
+        let last = blocks.len() - 1;
+        blocks[last].end = i + 1;
+        block_of[i] = last;
+    }
+
+    for b in 0..blocks.len() {
+        let (start, end) = (blocks[b].start, blocks[b].end);
+        let term = match end - 1 {
+            i if insts[i].delay_slot && i > start => i - 1,
+            i => i,
+        };
+        let inst = &insts[term];
+        let next = block_of.get(end).copied();
+        let mut succs = Vec::new();
+
+        match inst.branch {
+            Some(BranchInst::Jump(_)) => {
+                let uncond = always(mode, &inst.mnemonic);
+                let kind = if uncond {
+                    EdgeKind::Unconditional
+                } else {
+                    EdgeKind::True
+                };
+
+                // Tail calls leave the function
+                if let Some(&j) = analysis::jump_target(bin, inst).and_then(|x| index.get(&x)) {
+                    succs.push((block_of[j], kind));
+                }
+
+                if let (false, Some(n)) = (uncond, next) {
+                    succs.push((n, EdgeKind::False));
+                }
+            }
+            _ if !inst.cases.is_empty() => {
+                for c in &inst.cases {
+                    if let Some(&j) = index.get(c) {
+                        if !succs.iter().any(|(s, _)| *s == block_of[j]) {
+                            succs.push((block_of[j], EdgeKind::Unconditional));
+                        }
+                    }
+                }
+            }
+            _ if transfers(mode, inst) => {}
+            _ => succs.extend(next.map(|n| (n, EdgeKind::Unconditional))),
+        }
+
+        for (s, _) in &succs {
+            blocks[*s].preds.push(b);
+        }
+
+        blocks[b].succs = succs;
+    }
+
+    blocks
+}
+
+// Loop formed by back edges into header, blocks of body include header
+pub struct Loop {
+    pub header: usize,
+    // Blocks jumping back to header
+    pub latches: Vec<usize>,
+    pub body: Vec<usize>,
+}
+
+// Blocks reachable from entry in order they are finished in depth-first walk
+fn postorder(blocks: &[Block]) -> Vec<usize> {
+    let mut seen = vec![false; blocks.len()];
+    let mut order = Vec::new();
+    let mut stack = vec![(0, 0)];
+
+    if blocks.is_empty() {
+        return order;
+    }
+
+    seen[0] = true;
+
+    while let Some((b, i)) = stack.pop() {
+        match blocks[b].succs.get(i) {
+            Some((s, _)) => {
+                stack.push((b, i + 1));
+
+                if !seen[*s] {
+                    seen[*s] = true;
+                    stack.push((*s, 0));
+                }
+            }
+            None => order.push(b),
+        }
+    }
+
+    order
+}
+
+// Immediate dominator of every block, entry is its own and blocks not
+// reachable from entry have none. Iterative algorithm of Cooper, Harvey and
+// Kennedy
+pub fn dominators(blocks: &[Block]) -> Vec<Option<usize>> {
+    let order = postorder(blocks);
+    let mut index = vec![usize::MAX; blocks.len()];
+    let mut idom = vec![None; blocks.len()];
+
+    for (i, b) in order.iter().enumerate() {
+        index[*b] = i;
+    }
+
+    if order.is_empty() {
+        return idom;
+    }
+
+    idom[0] = Some(0);
+
+    let intersect = |idom: &[Option<usize>], mut a: usize, mut b: usize| {
+        while a != b {
+            while index[a] < index[b] {
+                a = idom[a].unwrap();
+            }
+            while index[b] < index[a] {
+                b = idom[b].unwrap();
+            }
+        }
+
+        a
+    };
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+
+        for b in order.iter().rev().skip(1) {
+            let mut new = None;
+
+            for p in &blocks[*b].preds {
+                if idom[*p].is_none() {
+                    continue;
+                }
+
+                new = Some(match new {
+                    None => *p,
+                    Some(n) => intersect(&idom, *p, n),
+                });
+            }
+
+            if new.is_some() && idom[*b] != new {
+                idom[*b] = new;
+                changed = true;
+            }
+        }
+    }
+
+    idom
+}
+
+fn dominates(idom: &[Option<usize>], a: usize, mut b: usize) -> bool {
+    loop {
+        if a == b {
+            return true;
+        }
+
+        match idom[b] {
+            Some(d) if d != b => b = d,
+            _ => return false,
+        }
+    }
+}
+
+// Natural loops, one per header, so loops sharing header are merged
+pub fn loops(blocks: &[Block]) -> Vec<Loop> {
+    let idom = dominators(blocks);
+    let mut loops: Vec<Loop> = Vec::new();
+
+    for (b, block) in blocks.iter().enumerate() {
+        if idom[b].is_none() {
+            continue;
+        }
+
+        for (h, _) in &block.succs {
+            if !dominates(&idom, *h, b) {
+                continue;
+            }
+
+            match loops.iter_mut().find(|l| l.header == *h) {
+                Some(l) if !l.latches.contains(&b) => l.latches.push(b),
+                Some(_) => {}
+                None => loops.push(Loop {
+                    header: *h,
+                    latches: vec![b],
+                    body: Vec::new(),
+                }),
+            }
+        }
+    }
+
+    // Body is everything reaching a latch without going through header
+    for l in &mut loops {
+        let mut body = vec![false; blocks.len()];
+        let mut work = l.latches.clone();
+
+        body[l.header] = true;
+
+        while let Some(b) = work.pop() {
+            if body[b] {
+                continue;
+            }
+
+            body[b] = true;
+            work.extend(blocks[b].preds.iter().filter(|p| idom[**p].is_some()));
+        }
+
+        l.body = (0..blocks.len()).filter(|b| body[*b]).collect();
+    }
+
+    loops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Blocks of one instruction each with successors given by index
+    fn graph(succs: &[&[usize]]) -> Vec<Block> {
+        let mut blocks: Vec<Block> = (0..succs.len())
+            .map(|i| Block {
+                start: i,
+                end: i + 1,
+                succs: succs[i]
+                    .iter()
+                    .map(|s| (*s, EdgeKind::Unconditional))
+                    .collect(),
+                preds: Vec::new(),
+            })
+            .collect();
+
+        for (b, s) in succs.iter().enumerate() {
+            for s in s.iter() {
+                blocks[*s].preds.push(b);
+            }
+        }
+
+        blocks
+    }
+
+    #[test]
+    fn nested_loops() {
+        let blocks = graph(&[&[1], &[2], &[3], &[2, 4], &[1, 5], &[]]);
+
+        assert_eq!(
+            dominators(&blocks),
+            vec![Some(0), Some(0), Some(1), Some(2), Some(3), Some(4)]
+        );
+
+        let loops = loops(&blocks);
+        assert_eq!(loops.len(), 2);
+        assert_eq!(
+            (loops[0].header, &loops[0].latches, &loops[0].body),
+            (2, &vec![3], &vec![2, 3])
+        );
+        assert_eq!(
+            (loops[1].header, &loops[1].latches, &loops[1].body),
+            (1, &vec![4], &vec![1, 2, 3, 4])
+        );
+    }
+
+    #[test]
+    fn self_loop_and_unreachable_block() {
+        // Block 3 jumps into the loop but nothing reaches it
+        let blocks = graph(&[&[1], &[1, 2], &[], &[1]]);
+
+        assert_eq!(dominators(&blocks), vec![Some(0), Some(0), Some(1), None]);
+
+        let loops = loops(&blocks);
+        assert_eq!(loops.len(), 1);
+        assert_eq!(
+            (loops[0].header, &loops[0].latches, &loops[0].body),
+            (1, &vec![1], &vec![1])
+        );
+    }
+
+    #[test]
+    fn edge_into_non_dominator_is_no_loop() {
+        // Both arms of the branch reach 3, which goes back into one of them
+        let blocks = graph(&[&[1, 2], &[3], &[3], &[2]]);
+
+        assert_eq!(
+            dominators(&blocks),
+            vec![Some(0), Some(0), Some(0), Some(0)]
+        );
+        assert!(loops(&blocks).is_empty());
+    }
+}
@@ -0,0 +1,171 @@
+use memmap::{Mmap, MmapOptions};
+use object::{File, Object};
+use std::path::{Path, PathBuf};
+
+// Stripped binaries keep their debug info in a separate file, that is looked
+// up the same way gdb does it: by build-id and by .gnu_debuglink
+const GLOBAL_DEBUG_DIR: &str = "/usr/lib/debug";
+
+pub struct DebugSearch {
+    pub file: PathBuf,
+    pub dirs: Vec<PathBuf>,
+}
+
+// CRC-32 used by .gnu_debuglink (the same as zlib)
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for b in data {
+        crc ^= *b as u32;
+
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
+
+fn map(path: &Path) -> Option<Mmap> {
+    let file = std::fs::File::open(path).ok()?;
+
+    unsafe { MmapOptions::new().map(&file) }.ok()
+}
+
+// Only the file that is picked stays mapped for the rest of the run
+fn keep(mmap: Mmap) -> &'static [u8] {
+    Box::leak(Box::new(mmap))
+}
+
+// <dir>/.build-id/ab/cdef....debug for build-id abcdef...
+fn build_id_path(dir: &Path, id: &[u8]) -> Option<PathBuf> {
+    let (first, rest) = id.split_first()?;
+    let name = rest
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+
+    Some(
+        dir.join(".build-id")
+            .join(format!("{:02x}", first))
+            .join(format!("{}.debug", name)),
+    )
+}
+
+impl DebugSearch {
+    pub fn new(file: &str, debug_dir: Option<&str>) -> Self {
+        let mut dirs: Vec<_> = debug_dir.into_iter().map(PathBuf::from).collect();
+
+        dirs.push(PathBuf::from(GLOBAL_DEBUG_DIR));
+
+        Self {
+            file: PathBuf::from(file),
+            dirs,
+        }
+    }
+
+    // File at build-id path may be left from an older build, so its own
+    // build-id has to match too
+    fn by_build_id(&self, id: &[u8]) -> Option<&'static [u8]> {
+        self.dirs
+            .iter()
+            .filter_map(|d| map(&build_id_path(d, id)?))
+            .find(|data| {
+                let own = File::parse(&data[..]).map(|o| o.build_id());
+                let ok = matches!(own, Ok(Ok(Some(b))) if b == id);
+
+                if !ok {
+                    warn!("Debug file of build-id has different build-id");
+                }
+
+                ok
+            })
+            .map(keep)
+    }
+
+    fn by_debuglink(&self, name: &[u8], crc: u32) -> Option<&'static [u8]> {
+        let name = std::str::from_utf8(name).ok()?;
+        let path = std::fs::canonicalize(&self.file).ok()?;
+        let dir = path.parent()?;
+
+        let mut candidates = vec![dir.join(name), dir.join(".debug").join(name)];
+
+        for d in self.dirs.iter() {
+            candidates.push(d.join(dir.strip_prefix("/").unwrap_or(dir)).join(name));
+            candidates.push(d.join(name));
+        }
+
+        candidates
+            .iter()
+            .filter(|p| **p != path)
+            .filter_map(|p| map(p))
+            .find(|data| {
+                let ok = crc32(data) == crc;
+
+                if !ok {
+                    warn!("Debug link {} has wrong CRC", name);
+                }
+
+                ok
+            })
+            .map(keep)
+    }
+
+    // Contents of debug file for the binary, if there is one
+    pub fn find(&self, data: &'static [u8]) -> Option<&'static [u8]> {
+        let obj = File::parse(data).ok()?;
+
+        if let Ok(Some(id)) = obj.build_id() {
+            if let Some(d) = self.by_build_id(id) {
+                return Some(d);
+            }
+        }
+
+        match obj.gnu_debuglink() {
+            Ok(Some((name, crc))) => self.by_debuglink(name, crc),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn build_id_path_splits_first_byte() {
+        let path = build_id_path(Path::new("/usr/lib/debug"), &[0xab, 0xcd, 0xef, 0x01]);
+
+        assert_eq!(
+            path,
+            Some(PathBuf::from("/usr/lib/debug/.build-id/ab/cdef01.debug"))
+        );
+        assert_eq!(build_id_path(Path::new("/"), &[]), None);
+    }
+
+    #[test]
+    fn stale_build_id_file_is_skipped() {
+        let dir = std::env::temp_dir().join(format!("disas-debug-{}", std::process::id()));
+        let id = [0x12, 0x34, 0x56];
+        let path = build_id_path(&dir, &id).unwrap();
+
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, b"not an object").unwrap();
+
+        let search = DebugSearch::new("/nonexistent", dir.to_str());
+        let found = search.by_build_id(&id);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(found.is_none());
+    }
+}
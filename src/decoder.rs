@@ -0,0 +1,466 @@
+use crate::analysis::{self, BranchInst};
+use crate::binary::{Arch, Binary, CodeKind, Ext, Function, Mode};
+use crate::bpf;
+use capstone::prelude::*;
+use std::cell::{Ref, RefCell};
+
+// Decoded instruction or data word. It owns everything, so code decoded by
+// different disassemblers can be shown in one listing
+pub struct Inst {
+    pub addr: u64,
+    pub bytes: Vec<u8>,
+    pub mnemonic: String,
+    pub op_str: String,
+    pub branch: Option<BranchInst>,
+    // Address of data instruction refers to
+    pub target: Option<u64>,
+    // Targets of jump table instruction jumps through
+    pub cases: Vec<u64>,
+    // Executed before the branch preceding it
+    pub delay_slot: bool,
+}
+
+// Assembly syntax of x86 listings
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum Syntax {
+    Intel,
+    Att,
+    Masm,
+}
+
+impl Syntax {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "intel" => Some(Self::Intel),
+            "att" => Some(Self::Att),
+            "masm" => Some(Self::Masm),
+            _ => None,
+        }
+    }
+
+    // Order in which syntax key goes through them
+    pub fn next(self) -> Self {
+        match self {
+            Self::Intel => Self::Att,
+            Self::Att => Self::Masm,
+            Self::Masm => Self::Intel,
+        }
+    }
+
+    fn capstone(self) -> capstone::Syntax {
+        match self {
+            Self::Intel => capstone::Syntax::Intel,
+            Self::Att => capstone::Syntax::Att,
+            Self::Masm => capstone::Syntax::Masm,
+        }
+    }
+}
+
+// Disassemblers are behind RefCell, since syntax changes while frames hold
+// shared reference to decoder
+pub struct Decoder {
+    mode: Mode,
+    // None for eBPF, which is decoded without capstone
+    cs: Option<RefCell<Capstone>>,
+    // ARM code switches between ARM and Thumb, this one decodes the other
+    // state
+    other: Option<RefCell<Capstone>>,
+}
+
+fn capstone(mode: Mode) -> Option<Capstone> {
+    let endian = if mode.big_endian && !mode.be8 {
+        capstone::Endian::Big
+    } else {
+        capstone::Endian::Little
+    };
+
+    let cs = match mode.arch {
+        Arch::X86 => Capstone::new()
+            .x86()
+            .mode(arch::x86::ArchMode::Mode32)
+            .detail(true)
+            .build(),
+        Arch::X86_64 => Capstone::new()
+            .x86()
+            .mode(arch::x86::ArchMode::Mode64)
+            .detail(true)
+            .build(),
+        Arch::Arm64 => Capstone::new()
+            .arm64()
+            .mode(arch::arm64::ArchMode::Arm)
+            .endian(endian)
+            .detail(true)
+            .build(),
+        Arch::Arm => Capstone::new()
+            .arm()
+            .mode(if mode.thumb {
+                arch::arm::ArchMode::Thumb
+            } else {
+                arch::arm::ArchMode::Arm
+            })
+            .endian(endian)
+            .detail(true)
+            .build(),
+        Arch::Riscv => Capstone::new()
+            .riscv()
+            .mode(if mode.bits64 {
+                arch::riscv::ArchMode::RiscV64
+            } else {
+                arch::riscv::ArchMode::RiscV32
+            })
+            .extra_mode(
+                Some(arch::riscv::ArchExtraMode::RiscVC)
+                    .filter(|_| mode.ext == Ext::RiscvC)
+                    .into_iter(),
+            )
+            .detail(true)
+            .build(),
+        Arch::Mips => Capstone::new()
+            .mips()
+            .mode(match mode.ext {
+                Ext::Mips2 => arch::mips::ArchMode::Mips2,
+                Ext::Mips3 => arch::mips::ArchMode::Mips3,
+                Ext::Mips32R6 => arch::mips::ArchMode::Mips32R6,
+                _ if mode.bits64 => arch::mips::ArchMode::Mips64,
+                _ => arch::mips::ArchMode::Mips32,
+            })
+            .extra_mode(
+                Some(arch::mips::ArchExtraMode::Micro)
+                    .filter(|_| mode.ext == Ext::MicroMips)
+                    .into_iter(),
+            )
+            .endian(endian)
+            .detail(true)
+            .build(),
+        Arch::Ppc => Capstone::new()
+            .ppc()
+            .mode(if mode.bits64 {
+                arch::ppc::ArchMode::Mode64
+            } else {
+                arch::ppc::ArchMode::Mode32
+            })
+            .endian(endian)
+            .detail(true)
+            .build(),
+        Arch::S390x => Capstone::new()
+            .sysz()
+            .mode(arch::sysz::ArchMode::Default)
+            .detail(true)
+            .build(),
+        Arch::Sparc => Capstone::new()
+            .sparc()
+            .mode(if mode.bits64 {
+                arch::sparc::ArchMode::V9
+            } else {
+                arch::sparc::ArchMode::Default
+            })
+            .detail(true)
+            .build(),
+        Arch::M68k => Capstone::new()
+            .m68k()
+            .mode(arch::m68k::ArchMode::M68k040)
+            .detail(true)
+            .build(),
+        Arch::Bpf | Arch::Unknown => {
+            error!("Unsupported architecture");
+            return None;
+        }
+    };
+
+    match cs {
+        Ok(cs) => Some(cs),
+        Err(e) => {
+            error!("Failed to create disassembler: {}", e);
+            None
+        }
+    }
+}
+
+impl Decoder {
+    pub fn new(mode: Mode, syntax: Syntax) -> Option<Self> {
+        let other = match mode.arch {
+            Arch::Arm => Some(RefCell::new(capstone(Mode {
+                thumb: !mode.thumb,
+                ..mode
+            })?)),
+            _ => None,
+        };
+
+        let cs = match mode.arch {
+            Arch::Bpf => None,
+            _ => Some(RefCell::new(capstone(mode)?)),
+        };
+
+        let dec = Self { mode, cs, other };
+
+        dec.set_syntax(syntax);
+        Some(dec)
+    }
+
+    // Only x86 has more than one syntax, false is returned for the rest
+    pub fn set_syntax(&self, syntax: Syntax) -> bool {
+        let cs = match &self.cs {
+            Some(cs) if matches!(self.mode.arch, Arch::X86 | Arch::X86_64) => cs,
+            _ => return false,
+        };
+
+        match cs.borrow_mut().set_syntax(syntax.capstone()) {
+            Ok(_) => true,
+            Err(e) => {
+                error!("Failed to change syntax: {}", e);
+                false
+            }
+        }
+    }
+
+    fn kind(&self) -> CodeKind {
+        if self.mode.thumb {
+            CodeKind::Thumb
+        } else {
+            CodeKind::Arm
+        }
+    }
+
+    fn capstone(&self, kind: CodeKind) -> Option<Ref<'_, Capstone>> {
+        match &self.other {
+            Some(other) if kind != self.kind() => Some(other.borrow()),
+            _ => self.cs.as_ref().map(|cs| cs.borrow()),
+        }
+    }
+
+    // Size of the smallest instruction, bytes which cannot be decoded are
+    // shown in such pieces
+    fn unit(&self, kind: CodeKind) -> usize {
+        match (self.mode.arch, kind) {
+            (Arch::X86 | Arch::X86_64, _) => 1,
+            (Arch::S390x | Arch::M68k, _) => 2,
+            (_, CodeKind::Thumb) => 2,
+            (Arch::Riscv, _) if self.mode.ext == Ext::RiscvC => 2,
+            (Arch::Mips, _) if self.mode.ext == Ext::MicroMips => 2,
+            (Arch::Bpf, _) => 8,
+            _ => 4,
+        }
+    }
+
+    fn data(&self, bytes: &[u8], addr: u64, unit: usize) -> Inst {
+        let value = if self.mode.big_endian {
+            bytes.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64)
+        } else {
+            bytes
+                .iter()
+                .rev()
+                .fold(0u64, |acc, b| (acc << 8) | *b as u64)
+        };
+
+        let mnemonic = match unit {
+            8 => ".quad",
+            4 => ".word",
+            2 => ".short",
+            _ => ".byte",
+        };
+
+        Inst {
+            addr,
+            bytes: bytes.to_vec(),
+            mnemonic: mnemonic.to_owned(),
+            op_str: format!("0x{:0width$x}", value, width = unit * 2),
+            branch: None,
+            target: None,
+            cases: Vec::new(),
+            delay_slot: false,
+        }
+    }
+
+    fn decode_data(&self, bytes: &[u8], addr: u64, out: &mut Vec<Inst>) {
+        let mut off = 0;
+
+        while off < bytes.len() {
+            let unit = if bytes.len() - off >= 4 { 4 } else { 1 };
+
+            out.push(self.data(&bytes[off..off + unit], addr + off as u64, unit));
+            off += unit;
+        }
+    }
+
+    fn decode_bpf(&self, bytes: &[u8], addr: u64, out: &mut Vec<Inst>) {
+        let mut off = 0;
+
+        while off < bytes.len() {
+            let at = addr + off as u64;
+
+            match bpf::decode(&bytes[off..], at, self.mode.big_endian) {
+                Some(inst) => {
+                    off += inst.bytes.len();
+                    out.push(inst);
+                }
+                None => {
+                    let unit = usize::min(self.unit(self.kind()), bytes.len() - off);
+
+                    out.push(self.data(&bytes[off..off + unit], at, unit));
+                    off += unit;
+                }
+            }
+        }
+    }
+
+    fn decode_code(
+        &self,
+        bin: &dyn Binary,
+        kind: CodeKind,
+        bytes: &[u8],
+        addr: u64,
+        out: &mut Vec<Inst>,
+    ) {
+        let cs = match self.capstone(kind) {
+            Some(cs) => cs,
+            None => return self.decode_bpf(bytes, addr, out),
+        };
+        let mut off = 0;
+        let mut delay_slot = false;
+        let mut tracker = analysis::Tracker::new(self.mode.arch);
+
+        while off < bytes.len() {
+            if let Ok(insns) = cs.disasm_all(&bytes[off..], addr + off as u64) {
+                for i in insns.iter() {
+                    let mut inst = Inst {
+                        addr: i.address(),
+                        bytes: i.bytes().to_vec(),
+                        mnemonic: i.mnemonic().unwrap_or("").to_owned(),
+                        op_str: i.op_str().unwrap_or("").to_owned(),
+                        branch: analysis::branch(&cs, self.mode, i),
+                        target: analysis::data_target(&cs, self.mode, i, &mut tracker),
+                        cases: Vec::new(),
+                        delay_slot,
+                    };
+
+                    if inst.branch.is_none() {
+                        if let Some(prev) = out.last() {
+                            // Target of relocated pair is only known to linker
+                            if analysis::reloc(bin, prev).is_none() {
+                                inst.branch = analysis::branch_pair(self.mode, prev, &inst);
+                            }
+                        }
+                    }
+
+                    if let Some(table) = tracker.jump.take() {
+                        inst.cases = analysis::table_targets(bin, self.mode, &table);
+                    }
+
+                    delay_slot = analysis::has_delay_slot(&cs, self.mode, i);
+                    out.push(inst);
+                    off += i.bytes().len();
+                }
+            }
+
+            // Decoder stops at the first invalid instruction
+            if off < bytes.len() {
+                let unit = usize::min(self.unit(kind), bytes.len() - off);
+
+                out.push(self.data(&bytes[off..off + unit], addr + off as u64, unit));
+                off += unit;
+                delay_slot = false;
+            }
+        }
+    }
+
+    pub fn decode(&self, bin: &dyn Binary, f: &Function) -> Vec<Inst> {
+        let (code, addr) = bin.func_code(f);
+        let end = addr + code.len() as u64;
+        let mut map = bin.code_map(addr, end);
+        let mut out = Vec::new();
+
+        if map.first().map(|m| m.0 > addr).unwrap_or(true) {
+            map.insert(0, (addr, self.kind()));
+        }
+
+        for (i, (start, kind)) in map.iter().enumerate() {
+            let stop = map.get(i + 1).map(|m| m.0).unwrap_or(end);
+            let bytes = &code[(start - addr) as usize..(stop - addr) as usize];
+
+            match kind {
+                CodeKind::Data => self.decode_data(bytes, *start, &mut out),
+                _ => self.decode_code(bin, *kind, bytes, *start, &mut out),
+            }
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::Section;
+
+    // ARM, then Thumb code and a literal pool, as told by mapping symbols
+    struct Mixed;
+
+    impl Binary for Mixed {
+        fn arch(&self) -> Arch {
+            Arch::Arm
+        }
+
+        fn function_by_addr(&self, _addr: u64) -> Option<Function> {
+            None
+        }
+
+        fn function_names(&self) -> Vec<Function> {
+            Vec::new()
+        }
+
+        fn sections(&self) -> Vec<Section> {
+            Vec::new()
+        }
+
+        fn func_code(&self, _f: &Function) -> (&[u8], u64) {
+            let code = &[
+                0x1e, 0xff, 0x2f, 0xe1, 0x70, 0x47, 0x00, 0xbf, 0x78, 0x56, 0x34, 0x12,
+            ];
+
+            (code, 0x1000)
+        }
+
+        fn code_map(&self, _start: u64, _end: u64) -> Vec<(u64, CodeKind)> {
+            vec![
+                (0x1000, CodeKind::Arm),
+                (0x1004, CodeKind::Thumb),
+                (0x1008, CodeKind::Data),
+            ]
+        }
+    }
+
+    #[test]
+    fn mapping_symbols_switch_decoder() {
+        let dec = Decoder::new(Mode::new(Arch::Arm), Syntax::Intel).unwrap();
+        let insts = dec.decode(&Mixed, &Function::new("f".to_owned(), 0x1000, 12));
+        let listing: Vec<_> = insts
+            .iter()
+            .map(|i| (i.addr, i.mnemonic.as_str(), i.op_str.as_str()))
+            .collect();
+
+        assert_eq!(
+            listing,
+            [
+                (0x1000, "bx", "lr"),
+                (0x1004, "bx", "lr"),
+                (0x1006, "nop", ""),
+                (0x1008, ".word", "0x12345678"),
+            ]
+        );
+    }
+
+    #[test]
+    fn be8_data_stays_big_endian() {
+        let mode = Mode {
+            big_endian: true,
+            be8: true,
+            ..Mode::new(Arch::Arm)
+        };
+        let dec = Decoder::new(mode, Syntax::Intel).unwrap();
+        let cs = dec.capstone(CodeKind::Arm).unwrap();
+        let insns = cs.disasm_all(&[0x1e, 0xff, 0x2f, 0xe1], 0).unwrap();
+
+        assert_eq!(insns.iter().next().unwrap().mnemonic(), Some("bx"));
+        assert_eq!(dec.data(&[1, 2, 3, 4], 0, 4).op_str, "0x01020304");
+    }
+}
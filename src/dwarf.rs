@@ -1,4 +1,4 @@
-use crate::elf::Function;
+use crate::binary::Function;
 use object::{File, Object, ObjectSection};
 use std::collections::HashMap;
 use std::ops::Range;
@@ -6,6 +6,7 @@ use std::path::PathBuf;
 use std::{borrow, path};
 
 pub struct DwarfParser {
+    #[allow(dead_code)]
     obj: File<'static>,
     addr_to_line: HashMap<Range<u64>, (PathBuf, usize)>,
     line_to_addr: HashMap<usize, Vec<Range<u64>>>,
@@ -18,6 +19,7 @@ pub struct FunctionDebugInfo {
 }
 
 impl FunctionDebugInfo {
+    #[allow(clippy::type_complexity)]
     pub(crate) fn new(
         data: (
             PathBuf,
@@ -44,6 +46,7 @@ impl FunctionDebugInfo {
         &self.path
     }
 
+    #[allow(dead_code)]
     pub fn line_range(&self) -> (usize, usize) {
         let mut min: usize = usize::MAX;
         let mut max: usize = 0;
@@ -103,7 +106,7 @@ impl DwarfParser {
             &'a borrow::Cow<[u8]>,
         )
             -> gimli::EndianSlice<'a, gimli::RunTimeEndian> =
-            &|section| gimli::EndianSlice::new(&*section, endian);
+            &|section| gimli::EndianSlice::new(section, endian);
 
         // Create `EndianSlice`s for all of the sections.
         let dwarf = dwarf_cow.borrow(&borrow_section);
@@ -170,15 +173,12 @@ impl DwarfParser {
         for i in tmp.as_slice().windows(2) {
             addr_to_line.insert(i[0].1..i[1].1, i[0].0.clone());
 
-            if let Some(line) = line_to_addr.get_mut(&i[0].0 .1) {
-                line.push(i[0].1..i[1].1);
-            } else {
-                line_to_addr.insert(i[0].0 .1, vec![(i[0].1..i[1].1)]);
-            }
+            line_to_addr
+                .entry(i[0].0 .1)
+                .or_default()
+                .push(i[0].1..i[1].1);
         }
 
-        use std::fs::write;
-
         Some(Self {
             obj,
             addr_to_line,
@@ -1,60 +1,140 @@
+use crate::binary::{Arch, Binary, CodeKind, Ext, Function, Import, Mode, Reloc, Section};
+use crate::bpf;
+use crate::debug_file::DebugSearch;
 use crate::dwarf::{DwarfParser, FunctionDebugInfo};
+use crate::plt;
+use crate::reloc;
+use crate::stripped;
 use elf::endian::AnyEndian;
-use elf::section::{SectionHeader, SectionHeaderTable};
-use elf::symbol::Symbol;
+use elf::section::SectionHeader;
+use elf::string_table::StringTable;
+use elf::symbol::{Symbol, SymbolTable};
 use elf::ElfBytes;
-use std::collections::HashMap;
-
-// Name and symbol
-pub struct Function(String, Symbol);
-type FunctionMap = HashMap<u64, Function>;
-
-// TODO: extend maybe?
-#[derive(PartialEq, Eq)]
-pub enum Arch {
-    X86,
-    X86_64,
-    Arm64,
-    Arm,
-    Riscv,
-    Mips,
-}
+use std::collections::{BTreeMap, HashMap};
 
-pub struct Elf {
-    data: ElfBytes<'static, AnyEndian>,
-    sections: SectionHeaderTable<'static, AnyEndian>,
-    functions: FunctionMap,
-    debug_info: Option<DwarfParser>,
-}
+// Function and index of section it is in
+type FunctionMap = HashMap<u64, (Function, u16)>;
+
+const ELF_SYM_STT_OBJECT: u8 = 1;
+const ELF_SYM_STT_FUNC: u8 = 2;
 
-impl Function {
-    pub fn new(name: String, sym: Symbol) -> Self {
-        Self(name, sym)
+// Address symbol points to. Low bit of ARM function marks Thumb code and is
+// not a part of address
+pub fn sym_addr(data: &ElfBytes<'static, AnyEndian>, section_addr: &[u64], sym: &Symbol) -> u64 {
+    let value = if data.ehdr.e_machine == elf::abi::EM_ARM && sym.st_symtype() == ELF_SYM_STT_FUNC {
+        sym.st_value & !1
+    } else {
+        sym.st_value
+    };
+
+    if data.ehdr.e_type == elf::abi::ET_REL {
+        section_addr
+            .get(sym.st_shndx as usize)
+            .copied()
+            .unwrap_or(0)
+            + value
+    } else {
+        value
     }
+}
 
-    pub fn name(&self) -> &String {
-        &self.0
+// ARM and AArch64 mark where code of each kind starts with $a, $t, $x and $d
+// symbols, optionally followed by a dot and anything. Without them ARM
+// function symbols still tell Thumb code by low bit
+fn mapping_symbols(
+    data: &ElfBytes<'static, AnyEndian>,
+    section_addr: &[u64],
+) -> BTreeMap<u64, CodeKind> {
+    let mut map = BTreeMap::new();
+
+    if !matches!(data.ehdr.e_machine, elf::abi::EM_ARM | elf::abi::EM_AARCH64) {
+        return map;
     }
 
-    pub fn addr(&self) -> u64 {
-        self.1.st_value
+    let (symtab, strtab) = match data.symbol_table() {
+        Ok(Some(t)) => t,
+        _ => return map,
+    };
+
+    for sym in symtab.iter().filter(|s| s.st_shndx != elf::abi::SHN_UNDEF) {
+        let name = strtab.get(sym.st_name as usize).unwrap_or("");
+        let kind = match name.split('.').next() {
+            Some("$a") | Some("$x") => CodeKind::Arm,
+            Some("$t") => CodeKind::Thumb,
+            Some("$d") => CodeKind::Data,
+            _ => continue,
+        };
+
+        map.insert(sym_addr(data, section_addr, &sym), kind);
     }
 
-    pub fn size(&self) -> usize {
-        self.1.st_size as usize
+    if data.ehdr.e_machine == elf::abi::EM_ARM {
+        for sym in symtab
+            .iter()
+            .filter(|s| s.st_symtype() == ELF_SYM_STT_FUNC && s.st_shndx != elf::abi::SHN_UNDEF)
+        {
+            let kind = if sym.st_value & 1 != 0 {
+                CodeKind::Thumb
+            } else {
+                CodeKind::Arm
+            };
+
+            map.entry(sym_addr(data, section_addr, &sym))
+                .or_insert(kind);
+        }
     }
+
+    map
 }
 
-impl Clone for Function {
-    fn clone(&self) -> Self {
-        Self::new(self.0.clone(), self.1.clone())
+// Named functions and data objects with their sizes, from both symbol tables
+fn data_symbols(
+    data: &ElfBytes<'static, AnyEndian>,
+    section_addr: &[u64],
+) -> BTreeMap<u64, (String, u64)> {
+    let mut map = BTreeMap::new();
+    let tables = [data.symbol_table(), data.dynamic_symbol_table()];
+
+    for (symtab, strtab) in tables.into_iter().filter_map(|t| t.ok().flatten()) {
+        for sym in symtab.iter().filter(|s| {
+            matches!(s.st_symtype(), ELF_SYM_STT_OBJECT | ELF_SYM_STT_FUNC)
+                && s.st_shndx != elf::abi::SHN_UNDEF
+        }) {
+            let name = match strtab.get(sym.st_name as usize) {
+                Ok(n) if !n.is_empty() => n.to_owned(),
+                _ => continue,
+            };
+
+            map.entry(sym_addr(data, section_addr, &sym))
+                .or_insert((name, sym.st_size));
+        }
     }
+
+    map
+}
+
+pub struct Elf {
+    raw_data: &'static [u8],
+    data: ElfBytes<'static, AnyEndian>,
+    sections: Vec<SectionHeader>,
+    section_addr: Vec<u64>,
+    functions: FunctionMap,
+    imports: HashMap<u64, Import>,
+    got: HashMap<u64, Import>,
+    relocs: BTreeMap<u64, Reloc>,
+    discovered: BTreeMap<u64, (Function, bool)>, // Function and whether size is known
+    mapping: BTreeMap<u64, CodeKind>,
+    symbols: BTreeMap<u64, (String, u64)>,
+    debug_info: Option<DwarfParser>,
 }
 
 impl Elf {
     pub fn new(raw_data: &'static [u8]) -> Option<Self> {
-        const ELF_SYM_STT_FUNC: u8 = 2;
+        Self::with_base(raw_data, 0)
+    }
 
+    // base is where sections of relocatable object start
+    pub fn with_base(raw_data: &'static [u8], base: u64) -> Option<Self> {
         let data = match ElfBytes::<AnyEndian>::minimal_parse(raw_data) {
             Ok(o) => Some(o),
             Err(e) => {
@@ -63,7 +143,76 @@ impl Elf {
             }
         }?;
 
-        let (symtab, strtab) = data.symbol_table().ok()??;
+        let sections: Vec<SectionHeader> = match data.section_headers() {
+            Some(s) => s.iter().collect(),
+            None => Self::segment_sections(&data),
+        };
+        let section_addr = Self::layout(&data, &sections, base);
+
+        let imports = plt::imports(&data);
+
+        let mut elf = Self {
+            functions: Self::functions(&data, &section_addr),
+            got: plt::got_imports(&data, &imports),
+            imports,
+            relocs: match data.ehdr.e_type {
+                elf::abi::ET_REL => reloc::relocations(&data, &sections, &section_addr),
+                _ => BTreeMap::new(),
+            },
+            mapping: mapping_symbols(&data, &section_addr),
+            symbols: data_symbols(&data, &section_addr),
+            sections,
+            section_addr,
+            data,
+            raw_data,
+            discovered: BTreeMap::new(),
+            debug_info: DwarfParser::new(raw_data),
+        };
+
+        elf.discover();
+        Some(elf)
+    }
+
+    // Function symbols of the file. Stripped files still have .dynsym with
+    // exported functions, and if both tables are present they are merged
+    fn functions(data: &ElfBytes<'static, AnyEndian>, section_addr: &[u64]) -> FunctionMap {
+        let mut functions = match data.symbol_table() {
+            Ok(Some((symtab, strtab))) => {
+                Self::table_functions(data, section_addr, &symtab, &strtab, "sym")
+            }
+            _ => FunctionMap::new(),
+        };
+
+        // Programs take place of symbols at start of their sections
+        if data.ehdr.e_machine == elf::abi::EM_BPF {
+            functions.extend(bpf::programs(data, section_addr));
+        }
+
+        let (dynsym, dynstr) = match data.dynamic_symbol_table() {
+            Ok(Some(t)) => t,
+            _ => return functions,
+        };
+
+        for (addr, f) in Self::table_functions(data, section_addr, &dynsym, &dynstr, "dyn") {
+            match functions.get_mut(&addr) {
+                Some(e) => e.0 = e.0.clone().tagged("sym+dyn"),
+                None => {
+                    functions.insert(addr, f);
+                }
+            }
+        }
+
+        functions
+    }
+
+    fn table_functions(
+        data: &ElfBytes<'static, AnyEndian>,
+        section_addr: &[u64],
+        symtab: &SymbolTable<'static, AnyEndian>,
+        strtab: &StringTable<'static>,
+        tag: &'static str,
+    ) -> FunctionMap {
+        let sym_addr = |sym: &Symbol| sym_addr(data, section_addr, sym);
 
         // If compiler does not set size for function, simply look up next label
         // in the same section
@@ -73,7 +222,7 @@ impl Elf {
             if sym.st_size == 0 {
                 for j in symtab.iter() {
                     if j.st_symtype() == ELF_SYM_STT_FUNC
-                        && sym.st_shndx == sym.st_shndx
+                        && j.st_shndx == sym.st_shndx
                         && j.st_value > sym.st_value
                     {
                         if let Some(s) = next_sym.as_ref() {
@@ -92,30 +241,224 @@ impl Elf {
             sym
         };
 
-        Some(Self {
-            functions: symtab
-                .iter()
-                .filter(|s| s.st_symtype() == ELF_SYM_STT_FUNC)
-                .map(|sym| {
+        symtab
+            .iter()
+            .filter(|s| s.st_symtype() == ELF_SYM_STT_FUNC && s.st_shndx != elf::abi::SHN_UNDEF)
+            .map(|sym| {
+                let sym = proccess_st_size(sym);
+                let name = strtab
+                    .get(sym.st_name as usize)
+                    .unwrap_or("unknown")
+                    .to_owned();
+
+                (
+                    sym_addr(&sym),
                     (
-                        sym.st_value,
-                        Function::new(
-                            strtab
-                                .get(sym.st_name as usize)
-                                .unwrap_or("unknown")
-                                .to_owned(),
-                            proccess_st_size(sym),
-                        ),
-                    )
-                })
-                .collect(),
-            sections: data.section_headers()?,
-            data,
-            debug_info: DwarfParser::new(raw_data),
+                        Function::new(name, sym_addr(&sym), sym.st_size as usize).tagged(tag),
+                        sym.st_shndx,
+                    ),
+                )
+            })
+            .collect()
+    }
+
+    // Section headers are not needed to run a program and can be stripped
+    // completely. Loadable segments take their place then
+    fn segment_sections(data: &ElfBytes<'static, AnyEndian>) -> Vec<SectionHeader> {
+        let segments = match data.segments() {
+            Some(s) => s,
+            None => return Vec::new(),
+        };
+
+        segments
+            .iter()
+            .filter(|p| p.p_type == elf::abi::PT_LOAD)
+            .map(|p| SectionHeader {
+                sh_name: 0,
+                sh_type: elf::abi::SHT_PROGBITS,
+                sh_flags: match p.p_flags & elf::abi::PF_X {
+                    0 => elf::abi::SHF_ALLOC as u64,
+                    _ => (elf::abi::SHF_ALLOC | elf::abi::SHF_EXECINSTR) as u64,
+                },
+                sh_addr: p.p_vaddr,
+                sh_offset: p.p_offset,
+                sh_size: p.p_filesz,
+                sh_link: 0,
+                sh_info: 0,
+                sh_addralign: p.p_align,
+                sh_entsize: 0,
+            })
+            .collect()
+    }
+
+    // Relocatable objects have every section at 0, so they are placed one
+    // after another the way linker would do it
+    fn layout(
+        data: &ElfBytes<'static, AnyEndian>,
+        sections: &[SectionHeader],
+        base: u64,
+    ) -> Vec<u64> {
+        if data.ehdr.e_type != elf::abi::ET_REL {
+            return sections.iter().map(|s| s.sh_addr).collect();
+        }
+
+        let mut addr = base;
+
+        sections
+            .iter()
+            .map(|s| {
+                if s.sh_flags & elf::abi::SHF_ALLOC as u64 == 0 {
+                    return 0;
+                }
+
+                let align = u64::max(s.sh_addralign, 1);
+                addr = addr.next_multiple_of(align);
+                addr += s.sh_size;
+
+                addr - s.sh_size
+            })
+            .collect()
+    }
+
+    fn code_section(&self, addr: u64) -> Option<&SectionHeader> {
+        self.sections.iter().find(|s| {
+            s.sh_flags & elf::abi::SHF_EXECINSTR as u64 != 0
+                && s.sh_addr <= addr
+                && addr < s.sh_addr + s.sh_size
         })
     }
 
-    pub fn arch(&self) -> Arch {
+    // Exported functions alone do not count, stripped binaries have them too
+    fn has_symtab(&self) -> bool {
+        self.functions.values().any(|f| f.0.tag() != Some("dyn"))
+    }
+
+    // Without symbol table functions have to be guessed
+    fn discover(&mut self) {
+        self.discovered.clear();
+
+        if self.has_symtab() || self.data.ehdr.e_type == elf::abi::ET_REL {
+            return;
+        }
+
+        for (addr, size) in stripped::function_starts(&self.data) {
+            if self.code_section(addr).is_none()
+                || self.functions.contains_key(&addr)
+                || self.imports.contains_key(&addr)
+            {
+                continue;
+            }
+
+            self.discovered.entry(addr).or_insert((
+                Function::new(format!("sub_{:x}", addr), addr, size.unwrap_or(0) as usize),
+                size.is_some(),
+            ));
+        }
+
+        self.fixup_discovered();
+    }
+
+    // Guessed functions without known size end where the next function starts
+    fn fixup_discovered(&mut self) {
+        let starts: Vec<u64> = self
+            .discovered
+            .keys()
+            .chain(self.functions.keys())
+            .copied()
+            .collect();
+
+        for (addr, (f, known)) in self.discovered.iter_mut() {
+            if *known {
+                continue;
+            }
+
+            let section_end = self
+                .sections
+                .iter()
+                .find(|s| s.sh_addr <= *addr && *addr < s.sh_addr + s.sh_size)
+                .map(|s| s.sh_addr + s.sh_size)
+                .unwrap_or(*addr);
+
+            let end = starts
+                .iter()
+                .copied()
+                .filter(|s| s > addr)
+                .fold(section_end, u64::min);
+
+            *f = f.clone().resized((end - addr) as usize);
+        }
+    }
+
+    // First address after the image
+    pub fn end(&self) -> u64 {
+        self.sections
+            .iter()
+            .zip(self.section_addr.iter())
+            .map(|(s, addr)| addr + s.sh_size)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn section_name(&self, s: &SectionHeader) -> String {
+        let strtab = match self.data.section_headers_with_strtab() {
+            Ok((_, Some(strtab))) => strtab,
+            _ => return String::new(),
+        };
+
+        strtab.get(s.sh_name as usize).unwrap_or("").to_owned()
+    }
+
+    // Symbols of SHN_ABS, SHN_COMMON and the like have no code in any section
+    fn func_code_reloc(&self, addr: u64) -> (&[u8], u64) {
+        let code = || -> Option<&[u8]> {
+            let (f, shndx) = self.functions.get(&addr)?;
+            let section = self.sections.get(*shndx as usize)?;
+            let section_data = self.data.section_data(section).ok()?.0;
+
+            let base = self.section_addr.get(*shndx as usize)?;
+            let start = f.addr().checked_sub(*base)? as usize;
+
+            section_data.get(start..start + f.size())
+        };
+
+        (code().unwrap_or(&[]), addr)
+    }
+
+    // Symbols may come from separate debug file and PLT stubs have no symbols
+    // at all, so section is looked up by address
+    fn func_code_exe(&self, f: &Function) -> (&[u8], u64) {
+        let target_section = self.sections.iter().find(|s| {
+            s.sh_type != elf::abi::SHT_NOBITS
+                && s.sh_flags & elf::abi::SHF_ALLOC as u64 != 0
+                && s.sh_addr <= f.addr()
+                && f.addr() < s.sh_addr + s.sh_size
+        });
+
+        let target_section = match target_section {
+            Some(s) => s,
+            None => return (&[], f.addr()),
+        };
+
+        let start = (f.addr() - target_section.sh_addr) as usize;
+        let end = start + f.size();
+
+        let section_data = match self.data.section_data(target_section) {
+            Ok((d, _)) => d,
+            Err(_) => return (&[], f.addr()),
+        };
+
+        if end > section_data.len() {
+            (&section_data[0..0], f.addr())
+        } else if start != end {
+            (&section_data[start..end], f.addr())
+        } else {
+            (&section_data[start..], f.addr())
+        }
+    }
+}
+
+impl Binary for Elf {
+    fn arch(&self) -> Arch {
         match self.data.ehdr.e_machine {
             0x3e => Arch::X86_64,
             0x03 => Arch::X86,
@@ -123,75 +466,429 @@ impl Elf {
             0x28 => Arch::Arm,
             0xF3 => Arch::Riscv,
             0x08 => Arch::Mips,
-            _ => panic!("Consider using some other arch... =)"),
+            0x14 | 0x15 => Arch::Ppc,
+            0x16 => Arch::S390x,
+            0x02 | 0x12 | 0x2b => Arch::Sparc,
+            0x04 => Arch::M68k,
+            0xf7 => Arch::Bpf,
+            _ => Arch::Unknown,
         }
     }
 
-    pub fn function_by_addr(&self, addr: u64) -> Option<Function> {
-        Some(self.functions.get(&addr)?.clone())
-    }
+    // Class and data encoding come from e_ident, ISA details from e_flags
+    fn mode(&self) -> Mode {
+        const EF_ARM_BE8: u32 = 0x0080_0000;
+        const EF_MIPS_ABI2: u32 = 0x0020;
+        const EF_MIPS_ARCH: u32 = 0xf000_0000;
+        const EF_MIPS_MICROMIPS: u32 = 0x0200_0000;
+        const EF_RISCV_RVC: u32 = 0x0001;
+
+        let flags = self.data.ehdr.e_flags;
+        let mut mode = Mode::new(self.arch());
+
+        mode.bits64 = self.data.ehdr.class == elf::file::Class::ELF64;
+        mode.big_endian = self.data.ehdr.endianness == AnyEndian::Big;
 
-    pub fn function_names(&self) -> Vec<Function> {
-        self.functions.iter().map(|x| (*x.1).clone()).collect()
+        match mode.arch {
+            // BE8 images have big endian data, but little endian code
+            Arch::Arm if flags & EF_ARM_BE8 != 0 => mode.be8 = mode.big_endian,
+            Arch::Riscv if flags & EF_RISCV_RVC != 0 => mode.ext = Ext::RiscvC,
+            // 32-bit SPARC binary using V9 instructions
+            Arch::Sparc if self.data.ehdr.e_machine == elf::abi::EM_SPARC32PLUS => {
+                mode.bits64 = true
+            }
+            Arch::Mips => {
+                let arch = (flags & EF_MIPS_ARCH) >> 28;
+
+                // n32 objects are 32-bit files with 64-bit code. Capstone
+                // cannot decode 64-bit R6, plain MIPS64 is the closest
+                mode.bits64 |= flags & EF_MIPS_ABI2 != 0 || arch == 10;
+                mode.ext = match arch {
+                    _ if flags & EF_MIPS_MICROMIPS != 0 => Ext::MicroMips,
+                    1 => Ext::Mips2,
+                    2 => Ext::Mips3,
+                    9 => Ext::Mips32R6,
+                    _ => Ext::None,
+                }
+            }
+            _ => {}
+        }
+
+        mode
     }
 
-    pub fn func_code(&self, addr: u64) -> (&[u8], u64) {
-        match self.data.ehdr.e_type {
-            elf::abi::ET_REL => self.func_code_reloc(addr),
-            elf::abi::ET_DYN | elf::abi::ET_EXEC => self.func_code_exe(addr),
-            _ => unreachable!(),
+    fn function_by_addr(&self, addr: u64) -> Option<Function> {
+        if let Some(f) = self.functions.get(&addr) {
+            return Some(f.0.clone());
         }
+
+        if let Some(f) = self.discovered.get(&addr) {
+            return Some(f.0.clone());
+        }
+
+        self.imports.get(&addr)?.stub.clone()
     }
 
-    fn func_code_reloc(&self, addr: u64) -> (&[u8], u64) {
-        let func = self.functions.get(&addr).unwrap();
-
-        return (
-            &self
-                .data
-                .section_data(&self.sections.get(func.1.st_shndx as usize).unwrap())
-                .unwrap()
-                .0[func.1.st_value as usize..func.1.st_size as usize],
-            func.1.st_value,
+    fn function_names(&self) -> Vec<Function> {
+        self.functions
+            .values()
+            .map(|x| x.0.clone())
+            .chain(self.discovered.values().map(|x| x.0.clone()))
+            .chain(self.imports.values().filter_map(|i| i.stub.clone()))
+            .collect()
+    }
+
+    fn add_function(&mut self, addr: u64) -> Option<Function> {
+        if let Some(f) = self.function_by_addr(addr) {
+            return Some(f);
+        }
+
+        // Code of relocatable object is found through symbols
+        if self.data.ehdr.e_type == elf::abi::ET_REL {
+            return None;
+        }
+
+        self.code_section(addr)?;
+        self.discovered.insert(
+            addr,
+            (Function::new(format!("sub_{:x}", addr), addr, 0), false),
         );
+        self.fixup_discovered();
+
+        Some(self.discovered.get(&addr)?.0.clone())
     }
 
-    fn func_code_exe(&self, addr: u64) -> (&[u8], u64) {
-        let func = self.functions.get(&addr).unwrap();
-        let sym = &func.1;
-        let target_section = &self.sections.get(sym.st_shndx as usize).unwrap();
+    fn stripped(&self) -> bool {
+        self.data.ehdr.e_type != elf::abi::ET_REL && !self.has_symtab()
+    }
 
-        let start = (sym.st_value - target_section.sh_addr) as usize;
-        let end = start + sym.st_size as usize;
+    fn import_by_addr(&self, addr: u64) -> Option<Import> {
+        self.imports.get(&addr).cloned()
+    }
 
-        let section_data = &self.data.section_data(target_section).unwrap().0;
-        // crate::log_info!("start {} end {} len {}", start, end, section_data.len());
-        if end > section_data.len() {
-            (&section_data[0..0], sym.st_value)
-        } else if start != end {
-            (&section_data[start..end], sym.st_value)
+    fn import_by_got(&self, addr: u64) -> Option<Import> {
+        self.got.get(&addr).cloned()
+    }
+
+    fn reloc_at(&self, addr: u64, size: usize) -> Option<Reloc> {
+        Some(
+            self.relocs
+                .range(addr..addr + size as u64)
+                .next()?
+                .1
+                .clone(),
+        )
+    }
+
+    fn symbol_at(&self, addr: u64) -> Option<(String, u64)> {
+        let (start, (name, size)) = self.symbols.range(..=addr).next_back()?;
+        let off = addr - start;
+
+        if off == 0 || off < *size {
+            Some((name.clone(), off))
         } else {
-            (&section_data[start..], sym.st_value)
+            None
         }
     }
 
-    pub fn function_debug_info(&self, f: &Function) -> Option<FunctionDebugInfo> {
+    fn bytes_at(&self, addr: u64) -> Option<&[u8]> {
+        let (s, start) =
+            self.sections
+                .iter()
+                .zip(self.section_addr.iter())
+                .find(|(s, start)| {
+                    s.sh_type != elf::abi::SHT_NOBITS
+                        && s.sh_flags & elf::abi::SHF_ALLOC as u64 != 0
+                        && **start <= addr
+                        && addr < **start + s.sh_size
+                })?;
+        let data = self.data.section_data(s).ok()?.0;
+
+        data.get((addr - start) as usize..)
+    }
+
+    fn code_map(&self, start: u64, end: u64) -> Vec<(u64, CodeKind)> {
+        let mut map: Vec<_> = self
+            .mapping
+            .range(..=start)
+            .next_back()
+            .map(|(_, k)| (start, *k))
+            .into_iter()
+            .collect();
+
+        if end > start {
+            map.extend(self.mapping.range(start + 1..end).map(|(a, k)| (*a, *k)));
+        }
+
+        map
+    }
+
+    fn sections(&self) -> Vec<Section> {
+        self.sections
+            .iter()
+            .zip(self.section_addr.iter())
+            .map(|(s, addr)| Section {
+                name: self.section_name(s),
+                addr: *addr,
+                size: s.sh_size,
+            })
+            .collect()
+    }
+
+    fn func_code(&self, f: &Function) -> (&[u8], u64) {
+        match self.data.ehdr.e_type {
+            elf::abi::ET_REL => self.func_code_reloc(f.addr()),
+            elf::abi::ET_DYN | elf::abi::ET_EXEC => self.func_code_exe(f),
+            // Core dumps and others have no functions of their own
+            _ => (&[], f.addr()),
+        }
+    }
+
+    fn function_debug_info(&self, f: &Function) -> Option<FunctionDebugInfo> {
         let dw = self.debug_info.as_ref()?;
 
-        dw.function_data(&f)
+        dw.function_data(f)
     }
 
-    #[cfg(debug_assertions)]
-    #[allow(dead_code)]
-    fn section_name(&self, s: &SectionHeader) {
-        let sh = self
-            .data
-            .section_header_by_name(".shstrtab")
-            .unwrap()
-            .unwrap();
-        println!(
-            "section {:?}",
-            std::str::from_utf8(&self.data.section_data(&sh).unwrap().0[s.sh_name as usize..])
-        );
+    // Debug file is produced by objcopy --only-keep-debug from the same
+    // binary, so its symbols have the same addresses. Code still comes from
+    // the binary itself
+    fn load_debug_file(&mut self, search: &DebugSearch) {
+        let raw_data = match search.find(self.raw_data) {
+            Some(d) => d,
+            None => return,
+        };
+
+        let data = match ElfBytes::<AnyEndian>::minimal_parse(raw_data) {
+            Ok(d) => d,
+            Err(e) => {
+                error!("Failed to parse debug file {}", e);
+                return;
+            }
+        };
+
+        let section_addr: Vec<_> = match data.section_headers() {
+            Some(s) => s.iter().map(|s| s.sh_addr).collect(),
+            None => return,
+        };
+
+        for (addr, f) in Self::functions(&data, &section_addr) {
+            self.functions.entry(addr).or_insert(f);
+        }
+
+        for (addr, sym) in data_symbols(&data, &section_addr) {
+            self.symbols.entry(addr).or_insert(sym);
+        }
+
+        if let Some(d) = DwarfParser::new(raw_data) {
+            self.debug_info = Some(d);
+        }
+
+        self.discover();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use elf::abi;
+
+    // ELF64 header of x86-64 file, program headers right after it
+    fn header(kind: u16, entry: u64, phnum: u16, shoff: u64, shnum: u16) -> Vec<u8> {
+        let mut h = vec![0x7f, b'E', b'L', b'F', 2, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        h.extend(kind.to_le_bytes());
+        h.extend(abi::EM_X86_64.to_le_bytes());
+        h.extend(1u32.to_le_bytes());
+        h.extend(entry.to_le_bytes());
+        h.extend(if phnum > 0 { 64u64 } else { 0 }.to_le_bytes());
+        h.extend(shoff.to_le_bytes());
+        h.extend(0u32.to_le_bytes());
+        h.extend([64, 0, 56, 0]);
+        h.extend(phnum.to_le_bytes());
+        h.extend(64u16.to_le_bytes());
+        h.extend(shnum.to_le_bytes());
+        h.extend(shnum.saturating_sub(1).to_le_bytes());
+        h
+    }
+
+    // name, type, flags, link, info, entsize and contents of section.
+    // .shstrtab is added as the last one
+    type Sec = (&'static str, u32, u64, u32, u32, u64, Vec<u8>);
+
+    fn object(sections: Vec<Sec>) -> &'static [u8] {
+        let mut names = vec![0u8];
+        let mut sections = sections;
+        let shstrtab =
+            names.len() as u32 + sections.iter().map(|s| s.0.len() as u32 + 1).sum::<u32>();
+
+        for s in &sections {
+            names.extend(s.0.as_bytes());
+            names.push(0);
+        }
+        names.extend(b".shstrtab\0");
+        sections.push((".shstrtab", abi::SHT_STRTAB, 0, 0, 0, 0, names));
+
+        let mut body = Vec::new();
+        let mut shdrs = vec![0u8; 64];
+        let mut name = 1u32;
+
+        for (i, (n, kind, flags, link, info, entsize, data)) in sections.iter().enumerate() {
+            let offset = 64 + body.len() as u64;
+            let sh_name = if i + 1 == sections.len() {
+                shstrtab
+            } else {
+                name
+            };
+
+            name += n.len() as u32 + 1;
+            body.extend(data);
+            body.resize((body.len() + 7) & !7, 0);
+            shdrs.extend(sh_name.to_le_bytes());
+            shdrs.extend(kind.to_le_bytes());
+            shdrs.extend(flags.to_le_bytes());
+            shdrs.extend(0u64.to_le_bytes());
+            shdrs.extend(offset.to_le_bytes());
+            shdrs.extend((data.len() as u64).to_le_bytes());
+            shdrs.extend(link.to_le_bytes());
+            shdrs.extend(info.to_le_bytes());
+            shdrs.extend(8u64.to_le_bytes());
+            shdrs.extend(entsize.to_le_bytes());
+        }
+
+        let count = sections.len() as u16 + 1;
+        let mut file = header(abi::ET_REL, 0, 0, 64 + body.len() as u64, count);
+        file.extend(body);
+        file.extend(shdrs);
+
+        Box::leak(file.into_boxed_slice())
+    }
+
+    // Function symbol entry of ELF64 symbol table
+    fn func(name: u32, shndx: u16, value: u64, size: u64) -> Vec<u8> {
+        let mut s = name.to_le_bytes().to_vec();
+        s.extend([0x12, 0]);
+        s.extend(shndx.to_le_bytes());
+        s.extend(value.to_le_bytes());
+        s.extend(size.to_le_bytes());
+        s
+    }
+
+    fn code_of(elf: &Elf, name: &str) -> usize {
+        let f = elf.function_names().into_iter().find(|f| f.name() == name);
+
+        elf.func_code(&f.unwrap()).0.len()
+    }
+
+    // Executable with single loadable segment and no section headers
+    fn sstripped(code: &[u8]) -> &'static [u8] {
+        let entry = 0x400000 + 64 + 56;
+        let size = (64 + 56 + code.len()) as u64;
+        let mut file = header(abi::ET_EXEC, entry, 1, 0, 0);
+
+        file.extend(abi::PT_LOAD.to_le_bytes());
+        file.extend((abi::PF_R | abi::PF_X).to_le_bytes());
+        file.extend(0u64.to_le_bytes());
+        file.extend(0x400000u64.to_le_bytes());
+        file.extend(0x400000u64.to_le_bytes());
+        file.extend(size.to_le_bytes());
+        file.extend(size.to_le_bytes());
+        file.extend(0x1000u64.to_le_bytes());
+        file.extend(code);
+
+        Box::leak(file.into_boxed_slice())
+    }
+
+    #[test]
+    fn object_functions_without_code() {
+        let mut symtab = vec![0; 24];
+        symtab.extend(func(1, 1, 0, 4));
+        symtab.extend(func(3, abi::SHN_ABS, 0x10, 8));
+        symtab.extend(func(5, abi::SHN_COMMON, 0x20, 8));
+        symtab.extend(func(12, 1, 2, 100));
+
+        let data = object(vec![
+            (".text", abi::SHT_PROGBITS, 6, 0, 0, 0, vec![0xc3; 4]),
+            (".symtab", abi::SHT_SYMTAB, 0, 3, 1, 24, symtab),
+            (
+                ".strtab",
+                abi::SHT_STRTAB,
+                0,
+                0,
+                0,
+                0,
+                b"\0f\0a\0common\0big\0".to_vec(),
+            ),
+        ]);
+        let elf = Elf::new(data).unwrap();
+
+        assert_eq!(code_of(&elf, "f"), 4);
+        assert_eq!(code_of(&elf, "a"), 0);
+        assert_eq!(code_of(&elf, "common"), 0);
+        assert_eq!(code_of(&elf, "big"), 0);
+    }
+
+    #[test]
+    fn segments_without_section_headers() {
+        let mut elf = Elf::new(sstripped(&[0x90, 0xc3, 0x90, 0xc3])).unwrap();
+        let functions = elf.function_names();
+
+        assert_eq!(elf.sections().len(), 1);
+        assert!(elf.stripped());
+        assert_eq!(functions.len(), 1);
+        assert_eq!(functions[0].name(), "sub_400078");
+        assert_eq!(elf.func_code(&functions[0]).0, &[0x90, 0xc3, 0x90, 0xc3]);
+
+        // Function defined inside of another one cuts it short
+        let f = elf.add_function(0x40007a).unwrap();
+
+        assert_eq!(elf.func_code(&f).0, &[0x90, 0xc3]);
+        assert_eq!(elf.function_by_addr(0x400078).unwrap().size(), 2);
+    }
+
+    // File header alone, which is enough to tell the mode
+    fn bare(class64: bool, big: bool, machine: u16, flags: u32) -> Elf {
+        let field = |v: u32, size: usize| match big {
+            true => v.to_be_bytes()[4 - size..].to_vec(),
+            false => v.to_le_bytes()[..size].to_vec(),
+        };
+        let (addr, ehsize, phentsize, shentsize) = match class64 {
+            true => (8, 64, 56, 64),
+            false => (4, 52, 32, 40),
+        };
+
+        let mut h = vec![0x7f, b'E', b'L', b'F', 1 + class64 as u8, 1 + big as u8, 1];
+        h.resize(16, 0);
+        h.extend(field(abi::ET_EXEC as u32, 2));
+        h.extend(field(machine as u32, 2));
+        h.extend(field(1, 4));
+        h.resize(h.len() + 3 * addr, 0);
+        h.extend(field(flags, 4));
+        h.extend(field(ehsize, 2));
+        h.extend(field(phentsize, 2));
+        h.extend(field(0, 2));
+        h.extend(field(shentsize, 2));
+        h.extend([0; 4]);
+
+        Elf::new(Box::leak(h.into_boxed_slice())).unwrap()
+    }
+
+    #[test]
+    fn arm_be8_code() {
+        let be8 = bare(false, true, abi::EM_ARM, 0x0580_0000).mode();
+        let be32 = bare(false, true, abi::EM_ARM, 0x0500_0000).mode();
+
+        assert!(be8.big_endian && be8.be8);
+        assert!(be32.big_endian && !be32.be8);
+    }
+
+    #[test]
+    fn mips_isa_from_flags() {
+        let n32 = bare(false, true, abi::EM_MIPS, 0x8000_0020).mode();
+        let r6 = bare(false, false, abi::EM_MIPS, 0x9000_0000).mode();
+        let r6_64 = bare(true, false, abi::EM_MIPS, 0xa000_0000).mode();
+
+        assert!(n32.bits64 && n32.ext == Ext::None);
+        assert!(!r6.bits64 && r6.ext == Ext::Mips32R6);
+        assert!(r6_64.bits64 && r6_64.ext == Ext::None);
     }
 }
@@ -0,0 +1,221 @@
+use crate::raw::Region;
+
+// Intel HEX and Motorola S-record images. Both are text files describing
+// chunks of memory, possibly with holes in between
+
+// Record payloads before they are glued into regions
+struct Image {
+    chunks: Vec<(u64, Vec<u8>)>,
+    entry: Option<u64>,
+}
+
+fn hex_bytes(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 == 1 {
+        return None;
+    }
+
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn be_value(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |acc, b| (acc << 8) | *b as u64)
+}
+
+impl Image {
+    fn new() -> Self {
+        Self {
+            chunks: Vec::new(),
+            entry: None,
+        }
+    }
+
+    // Adjacent chunks become one region, gaps start a new one
+    fn into_regions(mut self) -> (Vec<Region>, Option<u64>) {
+        let mut regions: Vec<(u64, Vec<u8>)> = Vec::new();
+
+        self.chunks.sort_by_key(|c| c.0);
+
+        for (addr, data) in self.chunks {
+            match regions.last_mut() {
+                Some(last) if last.0 + last.1.len() as u64 == addr => last.1.extend(data),
+                _ => regions.push((addr, data)),
+            }
+        }
+
+        (
+            regions
+                .into_iter()
+                .map(|(addr, data)| Region {
+                    addr,
+                    data: Box::leak(data.into_boxed_slice()),
+                })
+                .collect(),
+            self.entry,
+        )
+    }
+}
+
+fn parse_ihex(text: &str) -> Option<Image> {
+    let mut image = Image::new();
+    let mut base = 0u64;
+
+    for line in text.lines().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+        let rec = hex_bytes(line.strip_prefix(':')?)?;
+
+        // len, addr (2), type, data, checksum
+        if rec.len() < 5 || rec.len() != rec[0] as usize + 5 {
+            return None;
+        }
+
+        if rec.iter().fold(0u8, |acc, b| acc.wrapping_add(*b)) != 0 {
+            return None;
+        }
+
+        let addr = be_value(&rec[1..3]);
+        let data = &rec[4..rec.len() - 1];
+
+        match rec[3] {
+            0x00 => image.chunks.push((base + addr, data.to_vec())),
+            0x01 => break,
+            0x02 if data.len() == 2 => base = be_value(data) << 4,
+            0x03 if data.len() == 4 => {
+                image.entry = Some((be_value(&data[0..2]) << 4) + be_value(&data[2..4]))
+            }
+            0x04 if data.len() == 2 => base = be_value(data) << 16,
+            0x05 if data.len() == 4 => image.entry = Some(be_value(data)),
+            _ => return None,
+        }
+    }
+
+    Some(image)
+}
+
+fn parse_srec(text: &str) -> Option<Image> {
+    let mut image = Image::new();
+
+    for line in text.lines().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+        let kind = line.strip_prefix('S')?.chars().next()?;
+        let rec = hex_bytes(line.get(2..)?)?;
+
+        // count, addr, data, checksum
+        if rec.is_empty() || rec.len() != rec[0] as usize + 1 {
+            return None;
+        }
+
+        if rec.iter().fold(0u8, |acc, b| acc.wrapping_add(*b)) != 0xff {
+            return None;
+        }
+
+        let addr_len = match kind {
+            '0' | '1' | '5' | '9' => 2,
+            '2' | '6' | '8' => 3,
+            '3' | '7' => 4,
+            _ => return None,
+        };
+
+        if rec.len() < addr_len + 2 {
+            return None;
+        }
+
+        let addr = be_value(&rec[1..1 + addr_len]);
+        let data = &rec[1 + addr_len..rec.len() - 1];
+
+        match kind {
+            '1' | '2' | '3' => image.chunks.push((addr, data.to_vec())),
+            '7' | '8' | '9' => image.entry = Some(addr),
+            _ => {}
+        }
+    }
+
+    Some(image)
+}
+
+// Regions and entry point, if data is a valid HEX or S-record file
+pub fn parse(data: &[u8]) -> Option<(Vec<Region>, Option<u64>)> {
+    let text = std::str::from_utf8(data).ok()?;
+
+    let image = match text.trim_start().chars().next()? {
+        ':' => parse_ihex(text)?,
+        'S' => parse_srec(text)?,
+        _ => return None,
+    };
+
+    if image.chunks.is_empty() {
+        return None;
+    }
+
+    Some(image.into_regions())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn regions(data: &str) -> (Vec<(u64, Vec<u8>)>, Option<u64>) {
+        let (regions, entry) = parse(data.as_bytes()).unwrap();
+
+        (
+            regions.iter().map(|r| (r.addr, r.data.to_vec())).collect(),
+            entry,
+        )
+    }
+
+    #[test]
+    fn ihex_regions() {
+        let text = ":020000040800F2\n\
+                    :0401000001020304F1\n\
+                    :020104000506EE\n\
+                    :01020000AA53\n\
+                    :0400000508000131BD\n\
+                    :00000001FF\n";
+
+        assert_eq!(
+            regions(text),
+            (
+                vec![
+                    (0x0800_0100, vec![1, 2, 3, 4, 5, 6]),
+                    (0x0800_0200, vec![0xaa]),
+                ],
+                Some(0x0800_0131)
+            )
+        );
+    }
+
+    #[test]
+    fn ihex_bad_checksum() {
+        assert!(parse(b":0401000001020304F2\n:00000001FF\n").is_none());
+        assert!(parse(b":04010000010203F1\n").is_none());
+    }
+
+    #[test]
+    fn srec_regions() {
+        let text = "S0060000686472BB\n\
+                    S10510000102E7\n\
+                    S20500100203E5\n\
+                    S30720000000FFEEEB\n\
+                    S9031000EC\n";
+
+        assert_eq!(
+            regions(text),
+            (
+                vec![(0x1000, vec![1, 2, 3]), (0x2000_0000, vec![0xff, 0xee])],
+                Some(0x1000)
+            )
+        );
+    }
+
+    #[test]
+    fn srec_bad_checksum() {
+        assert!(parse(b"S10510000102E8\n").is_none());
+        assert!(parse(b"S4051000010203E7\n").is_none());
+    }
+
+    #[test]
+    fn not_an_image() {
+        assert!(parse(b"\x7fELF").is_none());
+        assert!(parse(b":00000001FF\n").is_none());
+    }
+}
@@ -0,0 +1,180 @@
+use crate::binary::{
+    self, fixup_sizes, object_arch, object_bytes, object_code, object_sections, object_symbol,
+    Arch, Binary, Function, Mode, Section,
+};
+use crate::dwarf::{DwarfParser, FunctionDebugInfo};
+use object::macho::{self as abi, MachHeader32};
+use object::read::macho::{FatArch, FatHeader, MachHeader};
+use object::{
+    Architecture, Endianness, File, FileKind, Object, ObjectSection, ObjectSymbol, SymbolKind,
+    SymbolMap, SymbolMapName,
+};
+use std::collections::HashMap;
+
+pub struct MachO {
+    obj: File<'static>,
+    arch: Arch,
+    functions: HashMap<u64, Function>,
+    symbols: SymbolMap<SymbolMapName<'static>>,
+    debug_info: Option<DwarfParser>,
+}
+
+// One architecture of universal binary
+pub struct FatSlice {
+    pub arch: String,
+    pub data: &'static [u8],
+}
+
+impl FatSlice {
+    // Whether slice holds code of the architecture mode is for
+    pub fn matches(&self, mode: Mode) -> bool {
+        match binary::load(self.data) {
+            Some(b) => b.arch() == mode.arch && b.mode().bits64 == mode.bits64,
+            None => false,
+        }
+    }
+}
+
+impl MachO {
+    pub fn new(raw_data: &'static [u8]) -> Option<Self> {
+        let obj = match File::parse(raw_data) {
+            Ok(o) => Some(o),
+            Err(e) => {
+                error!("Failed to parse file {}", e);
+                None
+            }
+        }?;
+
+        let text = obj.section_by_name("__text").map(|s| s.index());
+
+        // Mach-O nlist has no sizes, they are fixed up below
+        let mut funcs: Vec<_> = obj
+            .symbols()
+            .filter(|s| s.kind() == SymbolKind::Text && s.is_definition())
+            .filter(|s| text.is_none() || s.section_index() == text)
+            .filter_map(|s| Some(Function::new(s.name().ok()?.to_owned(), s.address(), 0)))
+            .collect();
+
+        funcs.sort_by_key(|f| f.addr());
+        funcs.dedup_by_key(|f| f.addr());
+        fixup_sizes(&mut funcs, &object_sections(&obj));
+
+        // object knows no 32-bit SPARC, which NeXTSTEP ran on as well
+        let arch = match obj.architecture() {
+            Architecture::Unknown if Self::cputype(raw_data) == Some(abi::CPU_TYPE_SPARC) => {
+                Arch::Sparc
+            }
+            a => object_arch(a),
+        };
+
+        Some(Self {
+            functions: funcs.into_iter().map(|f| (f.addr(), f)).collect(),
+            debug_info: DwarfParser::new(raw_data),
+            symbols: obj.symbol_map(),
+            arch,
+            obj,
+        })
+    }
+
+    fn cputype(data: &'static [u8]) -> Option<u32> {
+        let header = MachHeader32::<Endianness>::parse(data, 0).ok()?;
+
+        Some(header.cputype(header.endian().ok()?))
+    }
+
+    pub fn fat_slices(data: &'static [u8]) -> Option<Vec<FatSlice>> {
+        fn slices<A: FatArch>(data: &'static [u8], arches: &[A]) -> Vec<FatSlice> {
+            arches
+                .iter()
+                .filter_map(|a| {
+                    Some(FatSlice {
+                        arch: format!("{:?}", a.architecture()),
+                        data: a.data(data).ok()?,
+                    })
+                })
+                .collect()
+        }
+
+        match FileKind::parse(data).ok()? {
+            FileKind::MachOFat32 => Some(slices(data, FatHeader::parse_arch32(data).ok()?)),
+            FileKind::MachOFat64 => Some(slices(data, FatHeader::parse_arch64(data).ok()?)),
+            _ => None,
+        }
+    }
+}
+
+impl Binary for MachO {
+    fn arch(&self) -> Arch {
+        self.arch
+    }
+
+    // Old PowerPC slices are big endian
+    fn mode(&self) -> Mode {
+        Mode {
+            bits64: self.obj.is_64(),
+            big_endian: !self.obj.is_little_endian(),
+            ..Mode::new(self.arch())
+        }
+    }
+
+    fn function_by_addr(&self, addr: u64) -> Option<Function> {
+        Some(self.functions.get(&addr)?.clone())
+    }
+
+    fn function_names(&self) -> Vec<Function> {
+        self.functions.values().cloned().collect()
+    }
+
+    fn sections(&self) -> Vec<Section> {
+        object_sections(&self.obj)
+    }
+
+    fn func_code(&self, f: &Function) -> (&[u8], u64) {
+        (object_code(&self.obj, f.addr(), f.size()), f.addr())
+    }
+
+    fn function_debug_info(&self, f: &Function) -> Option<FunctionDebugInfo> {
+        let dw = self.debug_info.as_ref()?;
+
+        dw.function_data(f)
+    }
+
+    fn symbol_at(&self, addr: u64) -> Option<(String, u64)> {
+        object_symbol(&self.symbols, addr)
+    }
+
+    fn bytes_at(&self, addr: u64) -> Option<&[u8]> {
+        object_bytes(&self.obj, addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Header of big endian 32-bit executable without load commands
+    fn header(cputype: u32) -> &'static [u8] {
+        let mut data = Vec::new();
+
+        for field in [abi::MH_MAGIC, cputype, 0, abi::MH_EXECUTE, 0, 0, 0] {
+            data.extend(field.to_be_bytes());
+        }
+
+        Box::leak(data.into_boxed_slice())
+    }
+
+    #[test]
+    fn sparc_slice() {
+        let bin = MachO::new(header(abi::CPU_TYPE_SPARC)).unwrap();
+
+        assert!(bin.arch() == Arch::Sparc);
+        assert!(bin.mode().big_endian && !bin.mode().bits64);
+    }
+
+    #[test]
+    fn powerpc_slice() {
+        let bin = MachO::new(header(abi::CPU_TYPE_POWERPC)).unwrap();
+
+        assert!(bin.arch() == Arch::Ppc);
+    }
+}
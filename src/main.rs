@@ -1,10 +1,26 @@
 use memmap::MmapOptions;
 use std::env;
 
+mod analysis;
 mod app;
+mod archive;
+mod args;
+mod binary;
+mod bpf;
+mod cfg;
+mod debug_file;
+mod decoder;
+mod dwarf;
 mod elf;
+mod hex;
+mod macho;
+mod pe;
+mod plt;
+mod raw;
+mod reloc;
+mod stripped;
 mod term;
-mod dwarf;
+mod xref;
 
 #[macro_use]
 extern crate log;
@@ -13,17 +29,20 @@ extern crate capstone;
 fn main() {
     env_logger::init();
 
-    let args = env::args().collect::<Vec<String>>();
+    let argv = env::args().collect::<Vec<String>>();
 
-    if args.len() != 2 {
-        error!("usage: {} <file>", args[0]);
-        return;
-    }
+    let args = match args::Args::parse(&argv) {
+        Some(a) => a,
+        None => {
+            args::usage(&argv[0]);
+            return;
+        }
+    };
 
-    let file = match std::fs::File::open(&args[1]) {
+    let file = match std::fs::File::open(&args.file) {
         Ok(f) => Some(f),
         Err(e) => {
-            error!("Failed to open {}: {}", args[1], e);
+            error!("Failed to open {}: {}", args.file, e);
             None
         }
     }
@@ -32,22 +51,59 @@ fn main() {
     let mmap_data = match unsafe { MmapOptions::new().map(&file) } {
         Ok(f) => Some(f),
         Err(e) => {
-            error!("Failed to map {}: {}", args[1], e);
+            error!("Failed to map {}: {}", args.file, e);
             None
         }
     }
     .unwrap();
 
-    let e = match elf::Elf::new(Box::leak(Box::new(mmap_data))) {
-        Some(e) => Some(e),
-        None => {
-            error!("Failed to create elf");
-            None
+    let data: &'static [u8] = Box::leak(Box::new(mmap_data));
+
+    let search = debug_file::DebugSearch::new(&args.file, args.debug_dir.as_deref());
+
+    let fat = if args.raw {
+        None
+    } else {
+        macho::MachO::fat_slices(data)
+    };
+
+    // Universal binary is opened right at the slice --arch asks for
+    let (data, fat) = match (fat, args.mode) {
+        (Some(slices), Some(mode)) => match slices.into_iter().find(|s| s.matches(mode)) {
+            Some(s) => (s.data, None),
+            None => {
+                error!("{} has no slice for the --arch given", args.file);
+                return;
+            }
+        },
+        (fat, _) => (data, fat),
+    };
+
+    let mut app = if let Some(slices) = fat {
+        app::App::with_slices(slices, search, args.syntax)
+    } else {
+        let b: Option<Box<dyn binary::Binary>> = match (args.raw, args.mode) {
+            (true, Some(mode)) => Some(Box::new(raw::Raw::new(data, mode, args.base))),
+            (_, Some(mode)) => {
+                binary::load(data).map(|b| Box::new(binary::ModeOverride::new(b, mode)) as _)
+            }
+            _ => binary::load(data),
+        };
+
+        let mut b = match b {
+            Some(b) => Some(b),
+            None => {
+                error!("Failed to load {}", args.file);
+                None
+            }
         }
-    }
-    .unwrap();
+        .unwrap();
+
+        b.load_debug_file(&search);
+
+        app::App::new(b, args.syntax)
+    };
 
-    let mut app = app::App::new(e).unwrap();
     let mut tui = term::tui::Tui::new().unwrap();
 
     tui.draw(&mut app);
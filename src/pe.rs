@@ -0,0 +1,264 @@
+use crate::binary::{
+    fixup_sizes, object_arch, object_bytes, object_code, object_sections, object_symbol, Arch,
+    Binary, Function, Import, Section,
+};
+use crate::dwarf::{DwarfParser, FunctionDebugInfo};
+use object::pe::{ImageNtHeaders32, ImageNtHeaders64};
+use object::read::pe::{self, ImageNtHeaders, PeFile};
+use object::{
+    Architecture, File, LittleEndian as LE, Object, ObjectSection, ObjectSymbol, SectionKind,
+    SymbolKind, SymbolMap, SymbolMapName,
+};
+use std::collections::HashMap;
+
+pub struct Pe {
+    obj: File<'static>,
+    functions: HashMap<u64, Function>,
+    symbols: SymbolMap<SymbolMapName<'static>>,
+    debug_info: Option<DwarfParser>,
+    // Imports by their IAT slots and by thunks jumping through them
+    iat: HashMap<u64, Import>,
+    thunks: HashMap<u64, Import>,
+}
+
+impl Pe {
+    pub fn new(raw_data: &'static [u8]) -> Option<Self> {
+        let obj = match File::parse(raw_data) {
+            Ok(o) => Some(o),
+            Err(e) => {
+                error!("Failed to parse file {}", e);
+                None
+            }
+        }?;
+
+        let mut funcs: HashMap<u64, Function> = HashMap::new();
+        let mut add = |name: String, addr: u64, size: usize| {
+            // Symbols win over exports, exports over synthesized names
+            funcs
+                .entry(addr)
+                .or_insert_with(|| Function::new(name, addr, size));
+        };
+
+        // MinGW keeps COFF symbols around, MSVC images usually have none
+        for sym in obj.symbols() {
+            if sym.kind() == SymbolKind::Text && sym.is_definition() {
+                if let Ok(name) = sym.name() {
+                    add(name.to_owned(), sym.address(), sym.size() as usize);
+                }
+            }
+        }
+
+        if let Ok(exports) = obj.exports() {
+            for e in exports {
+                add(
+                    String::from_utf8_lossy(e.name()).into_owned(),
+                    e.address(),
+                    0,
+                );
+            }
+        }
+
+        for (begin, end) in Self::pdata(&obj) {
+            add(format!("sub_{:x}", begin), begin, (end - begin) as usize);
+        }
+
+        add("entry".to_owned(), obj.entry(), 0);
+
+        let sections = object_sections(&obj);
+        let mut funcs: Vec<_> = funcs
+            .into_values()
+            .filter(|f| {
+                obj.sections().any(|s| {
+                    s.kind() == SectionKind::Text
+                        && s.address() <= f.addr()
+                        && f.addr() < s.address() + s.size()
+                })
+            })
+            .collect();
+
+        fixup_sizes(&mut funcs, &sections);
+
+        let mut iat = match obj.is_64() {
+            true => Self::iat::<ImageNtHeaders64>(raw_data),
+            false => Self::iat::<ImageNtHeaders32>(raw_data),
+        };
+        let mut thunks = HashMap::new();
+
+        for f in &funcs {
+            let import = match Self::thunk_slot(&obj, f).and_then(|s| iat.get_mut(&s)) {
+                Some(i) => i,
+                None => continue,
+            };
+
+            import.stub = Some(f.clone());
+            import.section = sections
+                .iter()
+                .find(|s| s.addr <= f.addr() && f.addr() < s.addr + s.size)
+                .map_or(String::new(), |s| s.name.clone());
+            thunks.insert(f.addr(), import.clone());
+        }
+
+        Some(Self {
+            functions: funcs.into_iter().map(|f| (f.addr(), f)).collect(),
+            debug_info: DwarfParser::new(raw_data),
+            symbols: obj.symbol_map(),
+            obj,
+            iat,
+            thunks,
+        })
+    }
+
+    // Loader fills IAT slots with addresses of imported functions, which are
+    // then called through the slots
+    fn iat<Pe: ImageNtHeaders>(data: &'static [u8]) -> HashMap<u64, Import> {
+        let mut imports = HashMap::new();
+
+        let file = match PeFile::<Pe>::parse(data) {
+            Ok(f) => f,
+            Err(_) => return imports,
+        };
+        let table = match file.import_table() {
+            Ok(Some(t)) => t,
+            _ => return imports,
+        };
+        let mut descriptors = match table.descriptors() {
+            Ok(d) => d,
+            Err(_) => return imports,
+        };
+
+        let base = file.relative_address_base();
+        let width = std::mem::size_of::<Pe::ImageThunkData>() as u64;
+
+        while let Ok(Some(d)) = descriptors.next() {
+            let library = table
+                .name(d.name.get(LE))
+                .ok()
+                .map(|n| String::from_utf8_lossy(n).into_owned());
+
+            // Lookup table keeps names after IAT is overwritten by binding
+            let first = d.first_thunk.get(LE);
+            let lookup = match d.original_first_thunk.get(LE) {
+                0 => first,
+                a => a,
+            };
+
+            let mut thunks = match table.thunks(lookup) {
+                Ok(t) => t,
+                Err(_) => continue,
+            };
+            let mut slot = base + first as u64;
+
+            while let Ok(Some(thunk)) = thunks.next::<Pe>() {
+                let import = match table.import::<Pe>(thunk) {
+                    Ok(pe::Import::Name(_, n)) => {
+                        Some((String::from_utf8_lossy(n).into_owned(), "by name"))
+                    }
+                    Ok(pe::Import::Ordinal(o)) => Some((format!("ordinal_{}", o), "by ordinal")),
+                    Err(_) => None,
+                };
+
+                if let Some((name, reloc)) = import {
+                    imports.insert(
+                        slot,
+                        Import {
+                            name,
+                            stub: None,
+                            section: String::new(),
+                            got: slot,
+                            table: "IAT",
+                            reloc,
+                            version: None,
+                            library: library.clone(),
+                            needed: Vec::new(),
+                            local: None,
+                        },
+                    );
+                }
+
+                slot += width;
+            }
+        }
+
+        imports
+    }
+
+    // Thunk is `jmp [slot]`, the PE counterpart of PLT stub
+    fn thunk_slot(obj: &File<'static>, f: &Function) -> Option<u64> {
+        let code = object_bytes(obj, f.addr())?;
+        let disp = match code {
+            [0xff, 0x25, a, b, c, d, ..] => i32::from_le_bytes([*a, *b, *c, *d]),
+            _ => return None,
+        };
+
+        match obj.architecture() {
+            Architecture::X86_64 => Some((f.addr() as i64 + 6 + disp as i64) as u64),
+            Architecture::I386 => Some(disp as u32 as u64),
+            _ => None,
+        }
+    }
+
+    // x64 images describe every non-leaf function in the exception directory,
+    // which is the best we can get for stripped DLLs
+    fn pdata(obj: &File<'static>) -> Vec<(u64, u64)> {
+        if obj.architecture() != Architecture::X86_64 {
+            return Vec::new();
+        }
+
+        let data = match obj.section_by_name(".pdata").map(|s| s.data()) {
+            Some(Ok(d)) => d,
+            _ => return Vec::new(),
+        };
+
+        let base = obj.relative_address_base();
+        let rva = |b: &[u8]| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as u64 + base;
+
+        data.chunks_exact(12)
+            .map(|e| (rva(&e[0..4]), rva(&e[4..8])))
+            .filter(|(begin, end)| begin < end)
+            .collect()
+    }
+}
+
+impl Binary for Pe {
+    fn arch(&self) -> Arch {
+        object_arch(self.obj.architecture())
+    }
+
+    fn function_by_addr(&self, addr: u64) -> Option<Function> {
+        Some(self.functions.get(&addr)?.clone())
+    }
+
+    fn function_names(&self) -> Vec<Function> {
+        self.functions.values().cloned().collect()
+    }
+
+    fn sections(&self) -> Vec<Section> {
+        object_sections(&self.obj)
+    }
+
+    fn func_code(&self, f: &Function) -> (&[u8], u64) {
+        (object_code(&self.obj, f.addr(), f.size()), f.addr())
+    }
+
+    fn function_debug_info(&self, f: &Function) -> Option<FunctionDebugInfo> {
+        let dw = self.debug_info.as_ref()?;
+
+        dw.function_data(f)
+    }
+
+    fn import_by_addr(&self, addr: u64) -> Option<Import> {
+        self.thunks.get(&addr).cloned()
+    }
+
+    fn import_by_got(&self, addr: u64) -> Option<Import> {
+        self.iat.get(&addr).cloned()
+    }
+
+    fn symbol_at(&self, addr: u64) -> Option<(String, u64)> {
+        object_symbol(&self.symbols, addr)
+    }
+
+    fn bytes_at(&self, addr: u64) -> Option<&[u8]> {
+        object_bytes(&self.obj, addr)
+    }
+}
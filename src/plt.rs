@@ -0,0 +1,249 @@
+use crate::binary::{Function, Import};
+use elf::abi;
+use elf::endian::AnyEndian;
+use elf::gnu_symver::SymbolVersionTable;
+use elf::section::SectionHeader;
+use elf::string_table::StringTable;
+use elf::symbol::SymbolTable;
+use elf::ElfBytes;
+use std::collections::HashMap;
+
+// Calls to shared libraries go through PLT stubs, which jump through GOT
+// slots filled in by dynamic linker. Names of stubs come from relocations of
+// these slots
+
+const R_386_GLOB_DAT: u32 = 6;
+const R_386_JMP_SLOT: u32 = 7;
+
+// Relocation of GOT slot
+struct Slot {
+    addr: u64,
+    sym: usize,
+    jump_slot: bool,
+}
+
+// Jump slot and GLOB_DAT relocation types. RISC-V fills GOT with plain
+// absolute relocations, which can't be told from any other data
+fn slot_types(machine: u16) -> Option<(u32, Option<u32>)> {
+    match machine {
+        abi::EM_X86_64 => Some((abi::R_X86_64_JUMP_SLOT, Some(abi::R_X86_64_GLOB_DAT))),
+        abi::EM_386 => Some((R_386_JMP_SLOT, Some(R_386_GLOB_DAT))),
+        abi::EM_AARCH64 => Some((abi::R_AARCH64_JUMP_SLOT, Some(abi::R_AARCH64_GLOB_DAT))),
+        abi::EM_ARM => Some((abi::R_ARM_JUMP_SLOT, Some(abi::R_ARM_GLOB_DAT))),
+        abi::EM_RISCV => Some((abi::R_RISCV_JUMP_SLOT, None)),
+        _ => None,
+    }
+}
+
+// GOT slots in order of relocations, .rela.plt order matches order of stubs
+fn slots(data: &ElfBytes<'static, AnyEndian>) -> Vec<Slot> {
+    let (jump, glob) = match slot_types(data.ehdr.e_machine) {
+        Some(t) => t,
+        None => return Vec::new(),
+    };
+
+    let sections = match data.section_headers() {
+        Some(s) => s,
+        None => return Vec::new(),
+    };
+
+    let mut slots = Vec::new();
+
+    for s in sections.iter() {
+        let relocs: Vec<(u64, u32, u32)> = match s.sh_type {
+            abi::SHT_RELA => match data.section_data_as_relas(&s) {
+                Ok(r) => r.map(|r| (r.r_offset, r.r_sym, r.r_type)).collect(),
+                Err(_) => continue,
+            },
+            abi::SHT_REL => match data.section_data_as_rels(&s) {
+                Ok(r) => r.map(|r| (r.r_offset, r.r_sym, r.r_type)).collect(),
+                Err(_) => continue,
+            },
+            _ => continue,
+        };
+
+        slots.extend(
+            relocs
+                .into_iter()
+                .filter(|r| r.2 == jump || Some(r.2) == glob)
+                .map(|r| Slot {
+                    addr: r.0,
+                    sym: r.1 as usize,
+                    jump_slot: r.2 == jump,
+                }),
+        );
+    }
+
+    slots
+}
+
+fn section(data: &ElfBytes<'static, AnyEndian>, name: &str) -> Option<SectionHeader> {
+    data.section_header_by_name(name).ok()?
+}
+
+// x86 stubs are decoded: every one of them is `jmp *slot`, possibly after
+// endbr and push. That also covers .plt.got, which has no jump slots
+fn x86_stub_slot(data: &ElfBytes<'static, AnyEndian>, stub: &[u8], addr: u64) -> Option<u64> {
+    let pos = stub
+        .windows(2)
+        .position(|w| w[0] == 0xff && (w[1] == 0x25 || w[1] == 0xa3))?;
+    let disp = i32::from_le_bytes(stub.get(pos + 2..pos + 6)?.try_into().ok()?);
+
+    match (data.ehdr.e_machine, stub[pos + 1]) {
+        (abi::EM_X86_64, 0x25) => Some((addr as i64 + pos as i64 + 6 + disp as i64) as u64),
+        (abi::EM_386, 0x25) => Some(disp as u32 as u64),
+        // PIC code has GOT address in ebx
+        (abi::EM_386, 0xa3) => {
+            let got = section(data, ".got.plt").or_else(|| section(data, ".got"))?;
+
+            Some((got.sh_addr as i64 + disp as i64) as u64)
+        }
+        _ => None,
+    }
+}
+
+// Other arches use lazy .plt only: header followed by one stub per jump slot
+fn plt_layout(machine: u16) -> Option<(u64, u64)> {
+    match machine {
+        abi::EM_AARCH64 => Some((32, 16)),
+        abi::EM_ARM => Some((20, 12)),
+        abi::EM_RISCV => Some((32, 16)),
+        _ => None,
+    }
+}
+
+// Dynamic symbols and libraries imports are described with
+struct Dynamic {
+    dynsym: SymbolTable<'static, AnyEndian>,
+    dynstr: StringTable<'static>,
+    versions: Option<SymbolVersionTable<'static, AnyEndian>>,
+    needed: Vec<String>,
+}
+
+impl Dynamic {
+    fn new(data: &ElfBytes<'static, AnyEndian>) -> Option<Self> {
+        let (dynsym, dynstr) = data.dynamic_symbol_table().ok()??;
+
+        let needed: Vec<String> = match data.dynamic() {
+            Ok(Some(d)) => d
+                .iter()
+                .filter(|d| d.d_tag == abi::DT_NEEDED)
+                .filter_map(|d| dynstr.get(d.d_val() as usize).ok())
+                .map(|s| s.to_owned())
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        Some(Self {
+            versions: data.symbol_version_table().ok().flatten(),
+            dynsym,
+            dynstr,
+            needed,
+        })
+    }
+
+    fn import(&self, slot: &Slot, stub: Option<(u64, u64, &str)>) -> Option<Import> {
+        let sym = self.dynsym.get(slot.sym).ok()?;
+        let name = self.dynstr.get(sym.st_name as usize).ok()?.to_owned();
+        let req = self
+            .versions
+            .as_ref()
+            .and_then(|v| v.get_requirement(slot.sym).ok().flatten());
+
+        Some(Import {
+            stub: stub.map(|(addr, size, _)| {
+                Function::new(format!("{}@plt", name), addr, size as usize).tagged("plt")
+            }),
+            section: stub.map(|s| s.2).unwrap_or("").to_owned(),
+            got: slot.addr,
+            table: "GOT",
+            reloc: if slot.jump_slot {
+                "JUMP_SLOT"
+            } else {
+                "GLOB_DAT"
+            },
+            version: req.as_ref().map(|r| r.name.to_owned()),
+            library: req.as_ref().map(|r| r.file.to_owned()),
+            needed: self.needed.clone(),
+            local: Some(sym.st_value).filter(|_| sym.st_shndx != abi::SHN_UNDEF),
+            name,
+        })
+    }
+}
+
+// Imports by address of their stubs
+pub fn imports(data: &ElfBytes<'static, AnyEndian>) -> HashMap<u64, Import> {
+    let slots = slots(data);
+    let mut stubs: Vec<(u64, u64, &str, &Slot)> = Vec::new();
+    let machine = data.ehdr.e_machine;
+
+    if machine == abi::EM_X86_64 || machine == abi::EM_386 {
+        for name in [".plt", ".plt.sec", ".plt.got"] {
+            let s = match section(data, name) {
+                Some(s) => s,
+                None => continue,
+            };
+
+            let code = match data.section_data(&s) {
+                Ok((d, _)) => d,
+                Err(_) => continue,
+            };
+
+            let size = match s.sh_entsize {
+                0 if name == ".plt.got" => 8,
+                0 => 16,
+                n => n,
+            };
+
+            for (i, stub) in code.chunks_exact(size as usize).enumerate() {
+                let addr = s.sh_addr + i as u64 * size;
+                let slot = x86_stub_slot(data, stub, addr)
+                    .and_then(|got| slots.iter().find(|s| s.addr == got));
+
+                if let Some(slot) = slot {
+                    stubs.push((addr, size, name, slot));
+                }
+            }
+        }
+    } else if let (Some((header, size)), Some(s)) = (plt_layout(machine), section(data, ".plt")) {
+        for (i, slot) in slots.iter().filter(|s| s.jump_slot).enumerate() {
+            stubs.push((s.sh_addr + header + i as u64 * size, size, ".plt", slot));
+        }
+    }
+
+    let dynamic = match Dynamic::new(data) {
+        Some(d) => d,
+        None => return HashMap::new(),
+    };
+
+    stubs
+        .into_iter()
+        .filter_map(|(addr, size, section, slot)| {
+            Some((addr, dynamic.import(slot, Some((addr, size, section)))?))
+        })
+        .collect()
+}
+
+// Imports by their GOT slots. Code built with -fno-plt calls through the slot
+// directly, and data symbols have no stubs at all
+pub fn got_imports(
+    data: &ElfBytes<'static, AnyEndian>,
+    imports: &HashMap<u64, Import>,
+) -> HashMap<u64, Import> {
+    let dynamic = match Dynamic::new(data) {
+        Some(d) => d,
+        None => return HashMap::new(),
+    };
+
+    slots(data)
+        .iter()
+        .filter_map(|slot| {
+            let import = match imports.values().find(|i| i.got == slot.addr) {
+                Some(i) => i.clone(),
+                None => dynamic.import(slot, None)?,
+            };
+
+            Some((slot.addr, import))
+        })
+        .collect()
+}
@@ -0,0 +1,215 @@
+use crate::binary::{Arch, Binary, Function, Mode, Section};
+use crate::hex;
+use std::collections::BTreeMap;
+
+// Piece of memory image with known load address
+pub struct Region {
+    pub addr: u64,
+    pub data: &'static [u8],
+}
+
+// Headerless image: the only things known about it are the ones user told us
+pub struct Raw {
+    mode: Mode,
+    regions: Vec<Region>,
+    functions: BTreeMap<u64, Function>,
+}
+
+impl Region {
+    fn contains(&self, addr: u64) -> bool {
+        self.addr <= addr && addr < self.end()
+    }
+
+    fn end(&self) -> u64 {
+        self.addr + self.data.len() as u64
+    }
+
+    // Whole region shown as one function, so it can be disassembled linearly
+    fn as_function(&self) -> Function {
+        Function::new(format!("raw_{:x}", self.addr), self.addr, self.data.len())
+    }
+}
+
+impl Raw {
+    // Intel HEX and S-record files carry their own addresses, anything else
+    // is a flat image placed at base
+    pub fn new(data: &'static [u8], mode: Mode, base: u64) -> Self {
+        match hex::parse(data) {
+            Some((regions, entry)) => {
+                let mut raw = Self::from_regions(regions, mode);
+
+                // Thumb entry points have low bit set
+                if let Some(e) = entry {
+                    raw.add_function(if mode.thumb { e & !1 } else { e });
+                }
+
+                raw
+            }
+            None => Self::from_regions(vec![Region { addr: base, data }], mode),
+        }
+    }
+
+    pub fn from_regions(regions: Vec<Region>, mode: Mode) -> Self {
+        Self {
+            mode,
+            regions,
+            functions: BTreeMap::new(),
+        }
+    }
+
+    fn region(&self, addr: u64) -> Option<&Region> {
+        self.regions.iter().find(|r| r.contains(addr))
+    }
+
+    // User defined functions end where the next one starts. Ones outside
+    // of every region have no code and keep their size
+    fn fixup_sizes(&mut self) {
+        let starts: Vec<_> = self.functions.keys().copied().collect();
+
+        for (i, addr) in starts.iter().enumerate() {
+            let end = match self.region(*addr) {
+                Some(r) => r.end(),
+                None => continue,
+            };
+            let end = match starts.get(i + 1) {
+                Some(next) if *next < end => *next,
+                _ => end,
+            };
+
+            if let Some(f) = self.functions.get_mut(addr) {
+                *f = f.clone().resized((end - addr) as usize);
+            }
+        }
+    }
+}
+
+impl Binary for Raw {
+    fn arch(&self) -> Arch {
+        self.mode.arch
+    }
+
+    fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    fn function_by_addr(&self, addr: u64) -> Option<Function> {
+        if let Some(f) = self.functions.get(&addr) {
+            return Some(f.clone());
+        }
+
+        self.regions
+            .iter()
+            .find(|r| r.addr == addr)
+            .map(|r| r.as_function())
+    }
+
+    fn function_names(&self) -> Vec<Function> {
+        self.regions
+            .iter()
+            .map(|r| r.as_function())
+            .chain(self.functions.values().cloned())
+            .collect()
+    }
+
+    fn sections(&self) -> Vec<Section> {
+        self.regions
+            .iter()
+            .map(|r| Section {
+                name: format!("raw_{:x}", r.addr),
+                addr: r.addr,
+                size: r.data.len() as u64,
+            })
+            .collect()
+    }
+
+    fn func_code(&self, f: &Function) -> (&[u8], u64) {
+        let data = match self.region(f.addr()) {
+            Some(r) => {
+                let start = (f.addr() - r.addr) as usize;
+                let end = usize::min(start + f.size(), r.data.len());
+
+                &r.data[start..end]
+            }
+            None => &[],
+        };
+
+        (data, f.addr())
+    }
+
+    // Region starts already are functions, they are given back as they are
+    fn add_function(&mut self, addr: u64) -> Option<Function> {
+        if let Some(f) = self.function_by_addr(addr) {
+            return Some(f);
+        }
+
+        self.region(addr)?;
+
+        self.functions
+            .insert(addr, Function::new(format!("sub_{:x}", addr), addr, 0));
+        self.fixup_sizes();
+
+        self.functions.get(&addr).cloned()
+    }
+
+    fn bytes_at(&self, addr: u64) -> Option<&[u8]> {
+        let r = self.region(addr)?;
+
+        Some(&r.data[(addr - r.addr) as usize..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw() -> Raw {
+        let regions = vec![
+            Region {
+                addr: 0x1000,
+                data: &[0; 0x100],
+            },
+            Region {
+                addr: 0x2000,
+                data: &[0; 0x10],
+            },
+        ];
+
+        Raw::from_regions(regions, Mode::new(Arch::Arm))
+    }
+
+    #[test]
+    fn function_at_region_start_is_region() {
+        let mut raw = raw();
+
+        let f = raw.add_function(0x2000).unwrap();
+        assert_eq!((f.name().as_str(), f.size()), ("raw_2000", 0x10));
+        assert_eq!(raw.function_names().len(), 2);
+    }
+
+    #[test]
+    fn functions_end_at_next_one() {
+        let mut raw = raw();
+
+        raw.functions.insert(
+            0x1010,
+            Function::new("reset".to_owned(), 0x1010, 0).tagged("entry"),
+        );
+        raw.add_function(0x1080).unwrap();
+
+        let f = raw.function_by_addr(0x1010).unwrap();
+        assert_eq!((f.size(), f.tag()), (0x70, Some("entry")));
+        assert_eq!(raw.function_by_addr(0x1080).unwrap().size(), 0x80);
+        assert!(raw.add_function(0x3000).is_none());
+    }
+
+    #[test]
+    fn function_outside_regions() {
+        let mut raw = raw();
+
+        raw.functions
+            .insert(0x5000, Function::new("far".to_owned(), 0x5000, 4));
+        raw.add_function(0x1020).unwrap();
+
+        assert_eq!(raw.function_by_addr(0x5000).unwrap().size(), 4);
+    }
+}
@@ -0,0 +1,262 @@
+use crate::binary::Reloc;
+use crate::elf::sym_addr;
+use elf::abi;
+use elf::endian::AnyEndian;
+use elf::section::SectionHeader;
+use elf::ElfBytes;
+use std::collections::BTreeMap;
+
+// Code of relocatable object has zeroes wherever linker has to put an
+// address, so relocations are the only way to know what instruction refers to
+
+const STT_SECTION: u8 = 3;
+
+fn reloc_name(machine: u16, kind: u32) -> String {
+    let name = match (machine, kind) {
+        (abi::EM_X86_64, 1) => "R_X86_64_64",
+        (abi::EM_X86_64, 2) => "R_X86_64_PC32",
+        (abi::EM_X86_64, 4) => "R_X86_64_PLT32",
+        (abi::EM_X86_64, 9) => "R_X86_64_GOTPCREL",
+        (abi::EM_X86_64, 10) => "R_X86_64_32",
+        (abi::EM_X86_64, 11) => "R_X86_64_32S",
+        (abi::EM_X86_64, 41) => "R_X86_64_GOTPCRELX",
+        (abi::EM_X86_64, 42) => "R_X86_64_REX_GOTPCRELX",
+        (abi::EM_386, 1) => "R_386_32",
+        (abi::EM_386, 2) => "R_386_PC32",
+        (abi::EM_386, 4) => "R_386_PLT32",
+        (abi::EM_386, 9) => "R_386_GOTOFF",
+        (abi::EM_386, 10) => "R_386_GOTPC",
+        (abi::EM_AARCH64, 257) => "R_AARCH64_ABS64",
+        (abi::EM_AARCH64, 275) => "R_AARCH64_ADR_PREL_PG_HI21",
+        (abi::EM_AARCH64, 277) => "R_AARCH64_ADD_ABS_LO12_NC",
+        (abi::EM_AARCH64, 282) => "R_AARCH64_JUMP26",
+        (abi::EM_AARCH64, 283) => "R_AARCH64_CALL26",
+        (abi::EM_AARCH64, 286) => "R_AARCH64_LDST64_ABS_LO12_NC",
+        (abi::EM_AARCH64, 311) => "R_AARCH64_ADR_GOT_PAGE",
+        (abi::EM_AARCH64, 312) => "R_AARCH64_LD64_GOT_LO12_NC",
+        (abi::EM_ARM, 2) => "R_ARM_ABS32",
+        (abi::EM_ARM, 10) => "R_ARM_THM_CALL",
+        (abi::EM_ARM, 28) => "R_ARM_CALL",
+        (abi::EM_ARM, 29) => "R_ARM_JUMP24",
+        (abi::EM_ARM, 30) => "R_ARM_THM_JUMP24",
+        (abi::EM_ARM, 43) => "R_ARM_MOVW_ABS_NC",
+        (abi::EM_ARM, 44) => "R_ARM_MOVT_ABS",
+        (abi::EM_RISCV, 18) => "R_RISCV_CALL",
+        (abi::EM_RISCV, 19) => "R_RISCV_CALL_PLT",
+        (abi::EM_RISCV, 23) => "R_RISCV_PCREL_HI20",
+        (abi::EM_RISCV, 24) => "R_RISCV_PCREL_LO12_I",
+        (abi::EM_RISCV, 26) => "R_RISCV_HI20",
+        (abi::EM_RISCV, 27) => "R_RISCV_LO12_I",
+        (abi::EM_MIPS, 2) => "R_MIPS_32",
+        (abi::EM_MIPS, 4) => "R_MIPS_26",
+        (abi::EM_MIPS, 5) => "R_MIPS_HI16",
+        (abi::EM_MIPS, 6) => "R_MIPS_LO16",
+        (abi::EM_MIPS, 11) => "R_MIPS_CALL16",
+        (abi::EM_BPF, 1) => "R_BPF_64_64",
+        (abi::EM_BPF, 2) => "R_BPF_64_ABS64",
+        (abi::EM_BPF, 3) => "R_BPF_64_ABS32",
+        (abi::EM_BPF, 4) => "R_BPF_64_NODYLD32",
+        (abi::EM_BPF, 10) => "R_BPF_64_32",
+        _ => return format!("reloc type {}", kind),
+    };
+
+    name.to_owned()
+}
+
+// x86 PC-relative fields are relative to the end of 4-byte field, which is
+// compensated by -4 in addend
+fn target_bias(machine: u16, kind: u32) -> i64 {
+    match (machine, kind) {
+        (abi::EM_X86_64, 2) | (abi::EM_X86_64, 4) | (abi::EM_386, 2) | (abi::EM_386, 4) => 4,
+        _ => 0,
+    }
+}
+
+// Relocations of allocated sections by the address they patch
+pub fn relocations(
+    data: &ElfBytes<'static, AnyEndian>,
+    sections: &[SectionHeader],
+    section_addr: &[u64],
+) -> BTreeMap<u64, Reloc> {
+    let mut relocs = BTreeMap::new();
+    let machine = data.ehdr.e_machine;
+
+    let (symtab, strtab) = match data.symbol_table() {
+        Ok(Some(t)) => t,
+        _ => return relocs,
+    };
+
+    let shstrtab = match data.section_headers_with_strtab() {
+        Ok((_, Some(s))) => Some(s),
+        _ => None,
+    };
+
+    for s in sections.iter() {
+        let target = match sections.get(s.sh_info as usize) {
+            Some(t) if t.sh_flags & abi::SHF_ALLOC as u64 != 0 => s.sh_info as usize,
+            _ => continue,
+        };
+
+        let entries: Vec<(u64, u32, u32, i64)> = match s.sh_type {
+            abi::SHT_RELA => match data.section_data_as_relas(s) {
+                Ok(r) => r
+                    .map(|r| (r.r_offset, r.r_sym, r.r_type, r.r_addend))
+                    .collect(),
+                Err(_) => continue,
+            },
+            abi::SHT_REL => match data.section_data_as_rels(s) {
+                Ok(r) => r.map(|r| (r.r_offset, r.r_sym, r.r_type, 0)).collect(),
+                Err(_) => continue,
+            },
+            _ => continue,
+        };
+
+        for (offset, sym, kind, addend) in entries {
+            let sym = match symtab.get(sym as usize) {
+                Ok(s) => s,
+                Err(_) => continue,
+            };
+
+            let defined = sym.st_shndx != abi::SHN_UNDEF;
+            let addr = sym_addr(data, section_addr, &sym);
+
+            // Section symbols have no names, what they point to is given by
+            // addend
+            let (name, target_addr) = if sym.st_symtype() == STT_SECTION {
+                let section = sections.get(sym.st_shndx as usize);
+                let name = section
+                    .and_then(|sh| shstrtab.as_ref()?.get(sh.sh_name as usize).ok())
+                    .unwrap_or("section");
+                let off = addend + target_bias(machine, kind);
+
+                (
+                    match off {
+                        0 => name.to_owned(),
+                        _ => format!("{}+0x{:x}", name, off),
+                    },
+                    Some((addr as i64 + off) as u64),
+                )
+            } else {
+                (
+                    strtab
+                        .get(sym.st_name as usize)
+                        .unwrap_or("unknown")
+                        .to_owned(),
+                    if defined { Some(addr) } else { None },
+                )
+            };
+
+            relocs.insert(
+                section_addr[target] + offset,
+                Reloc {
+                    name,
+                    kind: reloc_name(machine, kind),
+                    target: target_addr,
+                },
+            );
+        }
+    }
+
+    relocs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // x86-64 object with `call foo` and `lea rax, [rip + .rodata+0x10]`
+    fn object() -> &'static [u8] {
+        let text = vec![
+            0xe8, 0, 0, 0, 0, 0x48, 0x8d, 0x05, 0, 0, 0, 0, 0xc3, 0, 0, 0,
+        ];
+        let rodata = vec![0; 32];
+        let rela = [(1u64, 2u64, 4u64, -4i64), (8, 1, 2, 0x10 - 4)]
+            .iter()
+            .flat_map(|(off, sym, kind, addend)| {
+                [
+                    off.to_le_bytes(),
+                    ((sym << 32) | kind).to_le_bytes(),
+                    addend.to_le_bytes(),
+                ]
+            })
+            .flatten()
+            .collect::<Vec<u8>>();
+        // null, section symbol of .rodata, undefined global foo
+        let mut symtab = vec![0; 24];
+        symtab.extend([0, 0, 0, 0, STT_SECTION, 0, 2, 0]);
+        symtab.extend([0; 16]);
+        symtab.extend([1, 0, 0, 0, 0x10, 0, 0, 0]);
+        symtab.extend([0; 16]);
+        let strtab = b"\0foo\0".to_vec();
+        let shstrtab = b"\0.text\0.rodata\0.rela.text\0.symtab\0.strtab\0.shstrtab\0".to_vec();
+
+        // name, type, flags, link, info, entsize
+        let headers = [
+            (1u32, abi::SHT_PROGBITS, 6u64, 0u32, 0u32, 0u64),
+            (7, abi::SHT_PROGBITS, 2, 0, 0, 0),
+            (15, abi::SHT_RELA, 0x40, 4, 1, 24),
+            (26, abi::SHT_SYMTAB, 0, 5, 2, 24),
+            (34, abi::SHT_STRTAB, 0, 0, 0, 0),
+            (42, abi::SHT_STRTAB, 0, 0, 0, 0),
+        ];
+
+        let mut body = Vec::new();
+        let mut shdrs = vec![0u8; 64];
+
+        for (data, (name, kind, flags, link, info, entsize)) in
+            [text, rodata, rela, symtab, strtab, shstrtab]
+                .iter()
+                .zip(headers)
+        {
+            let offset = 64 + body.len() as u64;
+
+            body.extend(data);
+            shdrs.extend(name.to_le_bytes());
+            shdrs.extend(kind.to_le_bytes());
+            shdrs.extend(flags.to_le_bytes());
+            shdrs.extend(0u64.to_le_bytes());
+            shdrs.extend(offset.to_le_bytes());
+            shdrs.extend((data.len() as u64).to_le_bytes());
+            shdrs.extend(link.to_le_bytes());
+            shdrs.extend(info.to_le_bytes());
+            shdrs.extend(8u64.to_le_bytes());
+            shdrs.extend(entsize.to_le_bytes());
+        }
+
+        body.resize((body.len() + 7) & !7, 0);
+
+        let mut file = vec![0x7f, b'E', b'L', b'F', 2, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        file.extend(abi::ET_REL.to_le_bytes());
+        file.extend(abi::EM_X86_64.to_le_bytes());
+        file.extend(1u32.to_le_bytes());
+        file.extend(0u64.to_le_bytes());
+        file.extend(0u64.to_le_bytes());
+        file.extend((64 + body.len() as u64).to_le_bytes());
+        file.extend(0u32.to_le_bytes());
+        file.extend([64, 0, 0, 0, 0, 0, 64, 0, 7, 0, 6, 0]);
+        file.extend(body);
+        file.extend(shdrs);
+
+        Box::leak(file.into_boxed_slice())
+    }
+
+    #[test]
+    fn symbol_and_section_targets() {
+        let data = ElfBytes::<AnyEndian>::minimal_parse(object()).unwrap();
+        let sections: Vec<_> = data.section_headers().unwrap().iter().collect();
+        let relocs = relocations(&data, &sections, &[0, 0x1000, 0x2000, 0, 0, 0, 0]);
+
+        let call = &relocs[&0x1001];
+        assert_eq!(call.name, "foo");
+        assert_eq!(call.kind, "R_X86_64_PLT32");
+        assert_eq!(call.target, None);
+
+        // PC-relative addend is compensated for the field size
+        let lea = &relocs[&0x1008];
+        assert_eq!(lea.name, ".rodata+0x10");
+        assert_eq!(lea.kind, "R_X86_64_PC32");
+        assert_eq!(lea.target, Some(0x2010));
+
+        assert_eq!(relocs.len(), 2);
+    }
+}
@@ -0,0 +1,157 @@
+use elf::abi;
+use elf::endian::AnyEndian;
+use elf::file::Class;
+use elf::section::SectionHeader;
+use elf::ElfBytes;
+use gimli::UnwindSection;
+use std::collections::HashMap;
+
+// Binary without any symbols still tells where some functions start: entry
+// point, constructors and unwind info, which also gives their sizes
+
+fn section(data: &ElfBytes<'static, AnyEndian>, name: &str) -> Option<SectionHeader> {
+    data.section_header_by_name(name).ok()?
+}
+
+fn endian(data: &ElfBytes<'static, AnyEndian>) -> gimli::RunTimeEndian {
+    match data.ehdr.endianness {
+        AnyEndian::Little => gimli::RunTimeEndian::Little,
+        AnyEndian::Big => gimli::RunTimeEndian::Big,
+    }
+}
+
+// Address and contents of .eh_frame. Without section headers it is found
+// through .eh_frame_hdr, which has a segment of its own
+fn eh_frame(data: &ElfBytes<'static, AnyEndian>) -> Option<(u64, &'static [u8])> {
+    if let Some(eh) = section(data, ".eh_frame") {
+        return Some((eh.sh_addr, data.section_data(&eh).ok()?.0));
+    }
+
+    let segments = data.segments()?;
+    let hdr = segments.iter().find(|p| p.p_type == abi::PT_GNU_EH_FRAME)?;
+    let word = match data.ehdr.class {
+        Class::ELF64 => 8,
+        Class::ELF32 => 4,
+    };
+
+    let bases = gimli::BaseAddresses::default().set_eh_frame_hdr(hdr.p_vaddr);
+    let parsed = gimli::EhFrameHdr::new(data.segment_data(&hdr).ok()?, endian(data))
+        .parse(&bases, word)
+        .ok()?;
+    let addr = match parsed.eh_frame_ptr() {
+        gimli::Pointer::Direct(a) => a,
+        gimli::Pointer::Indirect(_) => return None,
+    };
+
+    // Table is terminated, so the rest of segment can be given
+    let load = segments
+        .iter()
+        .find(|p| p.p_type == abi::PT_LOAD && p.p_vaddr <= addr && addr < p.p_vaddr + p.p_filesz)?;
+    let bytes = data.segment_data(&load).ok()?;
+
+    Some((addr, bytes.get((addr - load.p_vaddr) as usize..)?))
+}
+
+// Start and length of every FDE in .eh_frame
+fn fde_ranges(data: &ElfBytes<'static, AnyEndian>) -> Vec<(u64, u64)> {
+    let mut ranges = Vec::new();
+
+    let (addr, bytes) = match eh_frame(data) {
+        Some(e) => e,
+        None => return ranges,
+    };
+
+    let mut bases = gimli::BaseAddresses::default().set_eh_frame(addr);
+
+    if let Some(text) = section(data, ".text") {
+        bases = bases.set_text(text.sh_addr);
+    }
+
+    if let Some(got) = section(data, ".got") {
+        bases = bases.set_got(got.sh_addr);
+    }
+
+    let eh_frame = gimli::EhFrame::new(bytes, endian(data));
+    let mut entries = eh_frame.entries(&bases);
+
+    while let Ok(Some(e)) = entries.next() {
+        if let gimli::CieOrFde::Fde(partial) = e {
+            if let Ok(fde) = partial.parse(|s, b, o| s.cie_from_offset(b, o)) {
+                ranges.push((fde.initial_address(), fde.len()));
+            }
+        }
+    }
+
+    ranges
+}
+
+// Pointers of .init_array and .fini_array. In PIE they are zero and filled in
+// by relative relocations, so addends are taken instead
+fn array_entries(data: &ElfBytes<'static, AnyEndian>) -> Vec<u64> {
+    let word = match data.ehdr.class {
+        Class::ELF64 => 8,
+        Class::ELF32 => 4,
+    };
+
+    let mut addends = HashMap::new();
+
+    if let Some(sections) = data.section_headers() {
+        for s in sections.iter().filter(|s| s.sh_type == abi::SHT_RELA) {
+            if let Ok(relas) = data.section_data_as_relas(&s) {
+                addends.extend(relas.map(|r| (r.r_offset, r.r_addend as u64)));
+            }
+        }
+    }
+
+    let mut entries = Vec::new();
+
+    for name in [".init_array", ".fini_array"] {
+        let s = match section(data, name) {
+            Some(s) => s,
+            None => continue,
+        };
+
+        let bytes = match data.section_data(&s) {
+            Ok((d, _)) => d,
+            Err(_) => continue,
+        };
+
+        for (i, w) in bytes.chunks_exact(word).enumerate() {
+            let value = match data.ehdr.endianness {
+                AnyEndian::Little => w.iter().rev().fold(0, |acc, b| (acc << 8) | *b as u64),
+                AnyEndian::Big => w.iter().fold(0, |acc, b| (acc << 8) | *b as u64),
+            };
+
+            let addr = s.sh_addr + (i * word) as u64;
+
+            match value {
+                0 => entries.extend(addends.get(&addr)),
+                // -1 terminates some old style arrays
+                v if v == u64::MAX >> (64 - word * 8) => {}
+                v => entries.push(v),
+            }
+        }
+    }
+
+    entries
+}
+
+// Function starts with sizes, if known
+pub fn function_starts(data: &ElfBytes<'static, AnyEndian>) -> Vec<(u64, Option<u64>)> {
+    let mut starts: Vec<_> = fde_ranges(data)
+        .into_iter()
+        .map(|(addr, len)| (addr, Some(len)))
+        .collect();
+
+    starts.push((data.ehdr.e_entry, None));
+    starts.extend(array_entries(data).into_iter().map(|a| (a, None)));
+
+    // Low bit of ARM code pointer selects Thumb state
+    if data.ehdr.e_machine == abi::EM_ARM {
+        for s in starts.iter_mut() {
+            s.0 &= !1;
+        }
+    }
+
+    starts
+}
@@ -24,7 +24,7 @@ impl CommandLine {
         self.buffer.as_str()
     }
 
-    pub fn dump(&self) -> Paragraph {
+    pub fn dump(&self) -> Paragraph<'_> {
         Paragraph::new(self.buffer.clone())
     }
 
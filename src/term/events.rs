@@ -7,21 +7,26 @@ pub enum KeyboardEvent {
     Enter,
     Next,
     Prev,
+    Left,
+    Right,
     Exit,
-    PrevFrame,
     CmdEnter,
     CmdEnd,
     Delete,
     Help,
     HelpEnd,
+    DefineFunction,
+    SwitchSyntax,
+    Xrefs,
+    Graph,
+    CallTree,
 }
 
 fn do_wait_event() -> Option<KeyEvent> {
     loop {
-        match read().ok()? {
-            Event::Key(event) => return Some(event),
-            _ => {} // do nothing
-        };
+        if let Event::Key(event) = read().ok()? {
+            return Some(event);
+        }
     }
 }
 
@@ -43,12 +48,19 @@ pub fn wait_event(s: &State) -> KeyboardEvent {
                 KeyCode::Enter => Some(KeyboardEvent::Enter),
                 KeyCode::Up => Some(KeyboardEvent::Prev),
                 KeyCode::Down => Some(KeyboardEvent::Next),
+                KeyCode::Left => Some(KeyboardEvent::Left),
+                KeyCode::Right => Some(KeyboardEvent::Right),
                 KeyCode::Char('q') => Some(KeyboardEvent::Exit),
                 KeyCode::Char('k') => Some(KeyboardEvent::Prev),
                 KeyCode::Char('j') => Some(KeyboardEvent::Next),
-                KeyCode::Char('h') => Some(KeyboardEvent::PrevFrame),
-                KeyCode::Char('l') => Some(KeyboardEvent::Enter), /* vim-like shit */
+                KeyCode::Char('h') => Some(KeyboardEvent::Left),
+                KeyCode::Char('l') => Some(KeyboardEvent::Right), /* vim-like shit */
                 KeyCode::Char('/') => Some(KeyboardEvent::CmdEnter), /* vim-like shit */
+                KeyCode::Char('f') => Some(KeyboardEvent::DefineFunction),
+                KeyCode::Char('s') => Some(KeyboardEvent::SwitchSyntax),
+                KeyCode::Char('x') => Some(KeyboardEvent::Xrefs),
+                KeyCode::Char('g') => Some(KeyboardEvent::Graph),
+                KeyCode::Char('t') => Some(KeyboardEvent::CallTree),
                 KeyCode::F(1) => Some(KeyboardEvent::Help),
                 KeyCode::Esc => Some(KeyboardEvent::HelpEnd),
                 _ => None,
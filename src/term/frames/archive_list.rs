@@ -0,0 +1,70 @@
+use super::func_list::FuncList;
+use super::{ItemType, ScreenItem};
+use crate::binary::{Binary, Member};
+use crate::decoder::Decoder;
+use tui::{
+    style::{Color, Style},
+    widgets::{List, ListItem, ListState},
+};
+
+// Objects of static archive. First entry shows functions of all of them
+pub struct ArchiveList {
+    members: Vec<Member>,
+    ui_list: Vec<ListItem<'static>>,
+}
+
+impl ArchiveList {
+    pub fn new(members: Vec<Member>) -> Self {
+        Self {
+            ui_list: std::iter::once(ListItem::new("[all members]"))
+                .chain(
+                    members
+                        .iter()
+                        .map(|m| ListItem::new(format!("{} ({} symbols)", m.name, m.symbols))),
+                )
+                .collect(),
+            members,
+        }
+    }
+}
+
+impl ScreenItem for ArchiveList {
+    fn title(&self) -> String {
+        "Archive members".to_owned()
+    }
+
+    fn draw(&self) -> List<'_> {
+        List::new(self.ui_list.clone())
+            .style(Style::default().fg(Color::White))
+            .highlight_style(Style::default().bg(Color::DarkGray))
+    }
+
+    fn list_size(&self) -> usize {
+        self.members.len() + 1
+    }
+
+    fn go_in(
+        &mut self,
+        bin: &dyn Binary,
+        _dec: &'static Decoder,
+        state: &mut ListState,
+    ) -> Option<ItemType> {
+        let funcs = match state.selected()? {
+            0 => bin.function_names(),
+            i => self.members.get(i - 1)?.functions.clone(),
+        };
+
+        Some(ItemType::FunctionList(FuncList::new(funcs)))
+    }
+
+    fn find(&mut self, state: &mut ListState, s: &str) {
+        let start = state.selected().unwrap_or(0);
+
+        if let Some(i) = self.members[start.saturating_sub(1)..]
+            .iter()
+            .position(|m| m.name.contains(s))
+        {
+            state.select(Some(start.saturating_sub(1) + i + 1));
+        }
+    }
+}
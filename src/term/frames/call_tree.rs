@@ -0,0 +1,226 @@
+use super::func_asm::FuncAsm;
+use super::import_info::ImportInfo;
+use super::{ItemType, ScreenItem};
+use crate::binary::{Binary, Function};
+use crate::decoder::Decoder;
+use crate::term::events::KeyboardEvent;
+use crate::xref::{self, Xrefs};
+use tui::{
+    style::{Color, Style},
+    widgets::{List, ListItem, ListState},
+};
+
+// Deeper nodes are not expanded
+const MAX_DEPTH: usize = 32;
+
+struct Node {
+    func: Function,
+    depth: usize,
+    expanded: bool,
+    // Expanded without finding anything
+    leaf: bool,
+    // Function is already on the path from root
+    cycle: bool,
+}
+
+// Functions called from root, or calling it when inverted. Callers are not
+// followed past main
+pub struct CallTree {
+    callers: bool,
+    nodes: Vec<Node>,
+    ui_list: Vec<ListItem<'static>>,
+}
+
+impl CallTree {
+    pub fn new(
+        root: Function,
+        callers: bool,
+        bin: &dyn Binary,
+        dec: &Decoder,
+        xrefs: &Xrefs,
+    ) -> Self {
+        let mut s = Self {
+            callers,
+            nodes: vec![Node {
+                func: root,
+                depth: 0,
+                expanded: false,
+                leaf: false,
+                cycle: false,
+            }],
+            ui_list: Vec::new(),
+        };
+
+        s.render();
+        s.expand(0, bin, dec, xrefs);
+        s
+    }
+
+    // Tree in other direction rooted at selected function
+    pub fn inverted(
+        &self,
+        state: &ListState,
+        bin: &dyn Binary,
+        dec: &Decoder,
+        xrefs: &Xrefs,
+    ) -> Self {
+        let i = state.selected().unwrap_or(0);
+
+        Self::new(self.nodes[i].func.clone(), !self.callers, bin, dec, xrefs)
+    }
+
+    fn expandable(&self, i: usize) -> bool {
+        let n = &self.nodes[i];
+
+        let stop_at_main = self.callers && n.func.name() == "main";
+
+        !(n.cycle || n.leaf || stop_at_main || n.depth >= MAX_DEPTH)
+    }
+
+    fn expand(&mut self, i: usize, bin: &dyn Binary, dec: &Decoder, xrefs: &Xrefs) {
+        if self.nodes[i].expanded || !self.expandable(i) {
+            return;
+        }
+
+        let depth = self.nodes[i].depth;
+        let children = if self.callers {
+            xrefs.callers(self.nodes[i].func.addr())
+        } else {
+            xref::callees(bin, dec, &self.nodes[i].func)
+        };
+
+        // Functions from the node up to root
+        let mut path = vec![self.nodes[i].func.addr()];
+        let mut d = depth;
+        for n in self.nodes[..i].iter().rev() {
+            if n.depth < d {
+                path.push(n.func.addr());
+                d = n.depth;
+            }
+        }
+
+        let new: Vec<Node> = children
+            .into_iter()
+            .map(|f| Node {
+                cycle: path.contains(&f.addr()),
+                func: f,
+                depth: depth + 1,
+                expanded: false,
+                leaf: false,
+            })
+            .collect();
+
+        self.nodes[i].leaf = new.is_empty();
+        self.nodes[i].expanded = !new.is_empty();
+        self.nodes.splice(i + 1..i + 1, new);
+        self.render();
+    }
+
+    fn collapse(&mut self, i: usize) {
+        let depth = self.nodes[i].depth;
+        let end = self.nodes[i + 1..]
+            .iter()
+            .position(|n| n.depth <= depth)
+            .map_or(self.nodes.len(), |p| i + 1 + p);
+
+        self.nodes.drain(i + 1..end);
+        self.nodes[i].expanded = false;
+        self.render();
+    }
+
+    fn render(&mut self) {
+        self.ui_list = (0..self.nodes.len())
+            .map(|i| {
+                let n = &self.nodes[i];
+                let mark = if n.expanded {
+                    "-"
+                } else if self.expandable(i) {
+                    "+"
+                } else {
+                    " "
+                };
+                let note = if n.cycle { "  (recursive)" } else { "" };
+
+                ListItem::new(format!(
+                    "{}{} {}{}",
+                    "  ".repeat(n.depth),
+                    mark,
+                    n.func.name(),
+                    note
+                ))
+            })
+            .collect();
+    }
+
+    // Right expands node, Left collapses it or goes to its parent. Returns
+    // whether event was one of that
+    pub fn navigate(
+        &mut self,
+        e: &KeyboardEvent,
+        state: &mut ListState,
+        bin: &dyn Binary,
+        dec: &Decoder,
+        xrefs: &Xrefs,
+    ) -> bool {
+        let i = state.selected().unwrap_or(0);
+
+        match e {
+            KeyboardEvent::Right => self.expand(i, bin, dec, xrefs),
+            KeyboardEvent::Left if self.nodes[i].expanded => self.collapse(i),
+            KeyboardEvent::Left if i > 0 => {
+                let depth = self.nodes[i].depth;
+                let parent = self.nodes[..i].iter().rposition(|n| n.depth < depth);
+
+                state.select(parent);
+            }
+            _ => return false,
+        }
+
+        true
+    }
+}
+
+impl ScreenItem for CallTree {
+    fn title(&self) -> String {
+        match self.callers {
+            true => format!("Callers of {}", self.nodes[0].func.name()),
+            false => format!("Calls from {}", self.nodes[0].func.name()),
+        }
+    }
+
+    fn draw(&self) -> List<'_> {
+        List::new(self.ui_list.clone())
+            .style(Style::default().fg(Color::White))
+            .highlight_style(Style::default().bg(Color::DarkGray))
+    }
+
+    fn list_size(&self) -> usize {
+        self.nodes.len()
+    }
+
+    fn go_in(
+        &mut self,
+        bin: &dyn Binary,
+        dec: &'static Decoder,
+        state: &mut ListState,
+    ) -> Option<ItemType> {
+        let f = self.nodes.get(state.selected()?)?.func.clone();
+
+        if let Some(import) = bin.import_by_addr(f.addr()) {
+            return Some(ItemType::ImportInfo(ImportInfo::new(import)));
+        }
+
+        Some(ItemType::FunctionDisas(FuncAsm::new(f, bin, dec)))
+    }
+
+    fn find(&mut self, state: &mut ListState, s: &str) {
+        let start = state.selected().unwrap_or(0);
+
+        if let Some(i) = self.nodes[start..]
+            .iter()
+            .position(|n| n.func.name().contains(s))
+        {
+            state.select(Some(start + i));
+        }
+    }
+}
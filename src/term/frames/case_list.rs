@@ -0,0 +1,58 @@
+use super::{ItemType, ScreenItem};
+use crate::binary::Binary;
+use crate::decoder::Decoder;
+use tui::{
+    style::{Color, Style},
+    widgets::{List, ListItem, ListState},
+};
+
+// Cases of jump table, picked one is selected in the listing it came from
+pub struct CaseList {
+    jump: u64,
+    cases: Vec<u64>,
+    ui_list: Vec<ListItem<'static>>,
+}
+
+impl CaseList {
+    pub fn new(jump: u64, cases: Vec<u64>) -> Self {
+        Self {
+            ui_list: cases
+                .iter()
+                .enumerate()
+                .map(|(i, c)| ListItem::new(format!("case {:<8}0x{:x}", i, c)))
+                .collect(),
+            jump,
+            cases,
+        }
+    }
+
+    pub fn target(&self, state: &ListState) -> Option<u64> {
+        self.cases.get(state.selected()?).copied()
+    }
+}
+
+impl ScreenItem for CaseList {
+    fn title(&self) -> String {
+        format!("Jump table at 0x{:x}", self.jump)
+    }
+
+    fn draw(&self) -> List<'_> {
+        List::new(self.ui_list.clone())
+            .style(Style::default().fg(Color::White))
+            .highlight_style(Style::default().bg(Color::DarkGray))
+    }
+
+    fn list_size(&self) -> usize {
+        self.cases.len()
+    }
+
+    // Going to the case moves cursor of previous frame, so it is done by App
+    fn go_in(
+        &mut self,
+        _bin: &dyn Binary,
+        _dec: &'static Decoder,
+        _state: &mut ListState,
+    ) -> Option<ItemType> {
+        None
+    }
+}
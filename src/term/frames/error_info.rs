@@ -0,0 +1,45 @@
+use super::{ItemType, ScreenItem};
+use crate::binary::Binary;
+use crate::decoder::Decoder;
+use tui::{
+    style::{Color, Style},
+    widgets::{List, ListItem, ListState},
+};
+
+// Shown instead of a list when there is nothing to work with
+pub struct ErrorInfo {
+    ui_list: Vec<ListItem<'static>>,
+}
+
+impl ErrorInfo {
+    pub fn new(lines: Vec<String>) -> Self {
+        Self {
+            ui_list: lines.into_iter().map(ListItem::new).collect(),
+        }
+    }
+}
+
+impl ScreenItem for ErrorInfo {
+    fn title(&self) -> String {
+        "Error".to_owned()
+    }
+
+    fn draw(&self) -> List<'_> {
+        List::new(self.ui_list.clone())
+            .style(Style::default().fg(Color::Red))
+            .highlight_style(Style::default().bg(Color::DarkGray))
+    }
+
+    fn list_size(&self) -> usize {
+        self.ui_list.len()
+    }
+
+    fn go_in(
+        &mut self,
+        _bin: &dyn Binary,
+        _dec: &'static Decoder,
+        _state: &mut ListState,
+    ) -> Option<ItemType> {
+        None
+    }
+}
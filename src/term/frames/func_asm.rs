@@ -1,90 +1,199 @@
+use super::case_list::CaseList;
+use super::func_graph::{line_char, FuncGraph, DOWN, LEFT, RIGHT, UP};
+use super::import_info::ImportInfo;
 use super::{ItemType, ScreenItem};
+use crate::analysis::{self, BranchInst};
+use crate::binary::{Binary, Function};
+use crate::cfg::{self, Block};
+use crate::decoder::{Decoder, Inst};
 use crate::dwarf::FunctionDebugInfo;
-use crate::elf::{Arch, Elf, Function};
-use capstone::arch;
-use capstone::Capstone;
-use capstone::InsnGroupId;
-use capstone::InsnGroupType;
-use capstone::{Insn, Instructions};
-use itertools::Either;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{prelude::*, BufReader};
-use std::ops::Range;
 use tui::{
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
     text::{Line, Span, Text},
     widgets::{List, ListItem, ListState, Paragraph},
 };
 
-const CALL_INST: u8 = InsnGroupType::CS_GRP_CALL as u8;
-const JUMP_INST: u8 = InsnGroupType::CS_GRP_JUMP as u8;
-
 const COLORS: usize = 4;
 const BASE: u8 = 60;
 const SKIP: u8 = 10;
-
-enum BranchInst {
-    Call(u64),
-    Jump(u64),
-}
+// Longer strings are not shown
+const MAX_STRING: usize = 256;
+// Jump table targets shown next to the jump, Enter lists all of them
+const MAX_CASES_SHOWN: usize = 8;
+// Lanes of jump gutter, jumps that would need more are not drawn
+const MAX_LANES: usize = 12;
 
 lazy_static::lazy_static! {
     static ref STYLE_ARRAY: [Style; COLORS] = [
         Style::default().bg(Color::Rgb(BASE, BASE, BASE)),
-        Style::default().bg(Color::Rgb(BASE - SKIP * 1, BASE - SKIP * 1, BASE - SKIP * 1)),
+        Style::default().bg(Color::Rgb(BASE - SKIP, BASE - SKIP, BASE - SKIP)),
         Style::default().bg(Color::Rgb(BASE - SKIP * 2, BASE - SKIP * 2, BASE - SKIP * 2)),
         Style::default().bg(Color::Rgb(BASE - SKIP * 3, BASE - SKIP * 3 , BASE - SKIP * 3)),
     ];
 
     static ref STYLE_SELECTED: Style = Style::default().bg(Color::Blue);
+
+    static ref JUMP_COLORS: [Style; 6] = [
+        Style::default().fg(Color::Red),
+        Style::default().fg(Color::Green),
+        Style::default().fg(Color::Yellow),
+        Style::default().fg(Color::Blue),
+        Style::default().fg(Color::Magenta),
+        Style::default().fg(Color::Cyan),
+    ];
+
+    static ref JUMP_SELECTED: Style =
+        Style::default().fg(Color::White).add_modifier(Modifier::BOLD);
+}
+
+// Character of each gutter cell and jump drawn there, row per instruction
+type Gutter = Vec<Vec<(char, Option<usize>)>>;
+
+// Jump inside function between instructions of listing
+struct Jump {
+    from: usize,
+    to: usize,
+    lane: usize,
+    column: usize,
+}
+
+impl Jump {
+    fn covers(&self, row: usize, column: usize) -> bool {
+        let (a, b) = (self.from.min(self.to), self.from.max(self.to));
+
+        (column == self.column && a <= row && row <= b)
+            || ((row == a || row == b) && column >= self.column)
+    }
 }
 
 pub struct FuncAsm {
-    insn_list: Instructions<'static>,
+    insn_list: Vec<Inst>,
+    blocks: Vec<Block>,
+    // Loop nesting depth and mark of each instruction, empty without loops
+    loops: Vec<(usize, char)>,
     string_list: Vec<Text<'static>>,
-    name: String,
-    range_cleanup: Option<(Range<usize>, usize)>,
-    cs: &'static Capstone,
-    arch: Arch,
+    func: Function,
+    jumps: Vec<Jump>,
+    gutter: Gutter,
+    // Jump at cursor
+    highlight: Option<usize>,
     debug_info: Option<(Vec<Line<'static>>, usize)>,
     elf_debug_info: Option<FunctionDebugInfo>,
     marked: (Vec<usize>, Vec<usize>),
 }
 
 impl FuncAsm {
-    pub fn new(f: Function, elf: &Elf, cs: &'static Capstone) -> Self {
-        let (code, addr) = elf.func_code(f.addr());
-        let code = cs.disasm_all(code, addr).unwrap();
-        let elf_debug_info = elf.function_debug_info(&f);
+    pub fn new(f: Function, bin: &dyn Binary, dec: &'static Decoder) -> Self {
+        let code = dec.decode(bin, &f);
+        let elf_debug_info = bin.function_debug_info(&f);
         let debug_info = Self::debug_frame(&elf_debug_info);
+        let (jumps, gutter) = Self::jump_gutter(bin, &code);
+        let blocks = cfg::blocks(bin, &code);
 
         Self {
-            cs,
-            arch: elf.arch(),
-            name: (*f.name()).clone(),
-            string_list: code
-                .iter()
-                .map(|i| Self::inst_to_string(cs, elf, i, elf.arch()))
-                .collect(),
+            func: f,
+            string_list: code.iter().map(|i| Self::inst_to_string(bin, i)).collect(),
+            loops: Self::loop_gutter(&blocks, code.len()),
+            blocks,
             insn_list: code,
-            range_cleanup: None,
+            jumps,
+            gutter,
+            highlight: None,
             debug_info,
             elf_debug_info,
             marked: (Vec::new(), Vec::new()),
         }
     }
 
+    pub fn function(&self) -> &Function {
+        &self.func
+    }
+
+    pub fn addr_at(&self, state: &ListState) -> Option<u64> {
+        Some(self.insn_list.get(state.selected()?)?.addr)
+    }
+
+    // What instruction at cursor refers to: call, jump or data target
+    pub fn target_at(&self, bin: &dyn Binary, state: &ListState) -> Option<u64> {
+        let inst = self.insn_list.get(state.selected()?)?;
+
+        if let Some(r) = analysis::reloc(bin, inst) {
+            return r.target;
+        }
+
+        match inst.branch {
+            Some(BranchInst::Call(x)) | Some(BranchInst::Jump(x)) => Some(x),
+            Some(BranchInst::CallVia(x)) | Some(BranchInst::JumpVia(x)) => Some(x),
+            _ => inst.target,
+        }
+    }
+
+    // Graph of basic blocks with the one at cursor selected
+    pub fn graph(&self, state: &ListState) -> Option<FuncGraph> {
+        self.graph_of(state.selected()?)
+    }
+
+    // Graph with the block of instruction at addr selected
+    pub fn graph_at(&self, addr: u64) -> Option<FuncGraph> {
+        self.graph_of(self.insn_list.iter().position(|i| i.addr == addr)?)
+    }
+
+    fn graph_of(&self, idx: usize) -> Option<FuncGraph> {
+        let selected = self.blocks.iter().position(|b| b.end > idx)?;
+
+        Some(FuncGraph::new(
+            self.func.name().clone(),
+            &self.insn_list,
+            &self.blocks,
+            selected,
+        ))
+    }
+
+    // Moves cursor to instruction at addr, if the function has one
+    pub fn select_addr(&mut self, addr: u64, state: &mut ListState) {
+        if let Some(i) = self.insn_list.iter().position(|i| i.addr == addr) {
+            state.select(Some(i));
+            self.cursor_move(state);
+        }
+    }
+
+    // Decodes function again after decoder changed, cursor stays at the same
+    // instruction
+    pub fn reload(&mut self, bin: &dyn Binary, dec: &'static Decoder, state: &mut ListState) {
+        let addr = self.addr_at(state);
+
+        self.clean_debug();
+        self.insn_list = dec.decode(bin, &self.func);
+        (self.jumps, self.gutter) = Self::jump_gutter(bin, &self.insn_list);
+        self.blocks = cfg::blocks(bin, &self.insn_list);
+        self.loops = Self::loop_gutter(&self.blocks, self.insn_list.len());
+        self.string_list = self
+            .insn_list
+            .iter()
+            .map(|i| Self::inst_to_string(bin, i))
+            .collect();
+
+        if let Some(i) = addr.and_then(|a| self.insn_list.iter().position(|i| i.addr == a)) {
+            state.select(Some(i));
+        }
+
+        self.cursor_move(state);
+    }
+
     fn debug_frame(d: &Option<FunctionDebugInfo>) -> Option<(Vec<Line<'static>>, usize)> {
         let di = d.as_ref()?;
         let mut v = Vec::new();
         // let (start, end) = di.line_range();
 
-        if let Some(file) = File::open(di.file_name()).ok() {
+        if let Ok(file) = File::open(di.file_name()) {
             let reader = BufReader::new(file);
             // let mut size = end - start;
 
             // FIXME: Any idea how to get slice out of Lines?
-            for line in reader.lines().skip(0) {
+            for line in reader.lines() {
                 let l = Line::from(line.ok()?);
 
                 // l.patch_style(STYLE_ARRAY[i % COLORS]);
@@ -97,253 +206,292 @@ impl FuncAsm {
             }
         }
 
-        if v.len() != 0 {
+        if !v.is_empty() {
             Some((v, 0))
         } else {
             None
         }
     }
 
-    fn format_insn(i: &Insn) -> Vec<Span<'static>> {
-        let res = format!("0x{0: <30x}", i.address());
+    fn format_insn(i: &Inst) -> Vec<Span<'static>> {
+        let res = format!("0x{:<16x}", i.addr);
         let mut text = vec![Span::from(res)];
 
-        if let Some(mnemonic) = i.mnemonic() {
+        if !i.mnemonic.is_empty() {
             let style = Style::default().fg(Color::Cyan);
 
-            text.push(Span::styled(format!("{:6} ", mnemonic), style));
+            text.push(Span::styled(format!("{:6} ", i.mnemonic), style));
 
-            if let Some(op_str) = i.op_str() {
-                let style = Style::default().fg(Color::Magenta);
-                text.push(Span::styled(op_str.to_string(), style));
-            }
+            let style = Style::default().fg(Color::Magenta);
+            text.push(Span::styled(i.op_str.clone(), style));
         }
 
         text
     }
 
-    fn is_branch_inst(&self, inst: &Insn) -> Option<BranchInst> {
-        let detail = self.cs.insn_detail(inst).ok()?;
-        let group = detail.groups();
-
-        for i in group {
-            match i {
-                InsnGroupId(CALL_INST) => {
-                    for op in detail.arch_detail().operands() {
-                        // I wanna generate it using macros one day
-                        match self.arch {
-                            Arch::X86_64 | Arch::X86 => {
-                                if let arch::ArchOperand::X86Operand(op) = op {
-                                    if let arch::x86::X86OperandType::Imm(x) = op.op_type {
-                                        return Some(BranchInst::Call(x as u64));
-                                    }
-                                }
-                            }
-                            Arch::Arm => {
-                                if let arch::ArchOperand::ArmOperand(op) = op {
-                                    if let arch::arm::ArmOperandType::Imm(x) = op.op_type {
-                                        return Some(BranchInst::Call(x as u64));
-                                    }
-                                }
-                            }
-                            Arch::Arm64 => {
-                                if let arch::ArchOperand::Arm64Operand(op) = op {
-                                    if let arch::arm64::Arm64OperandType::Imm(x) = op.op_type {
-                                        return Some(BranchInst::Call(x as u64));
-                                    }
-                                }
-                            }
-                            _ => todo!(),
-                        }
-                    }
-                }
-                InsnGroupId(JUMP_INST) => {
-                    for op in detail.arch_detail().operands() {
-                        match self.arch {
-                            Arch::X86_64 | Arch::X86 => {
-                                if let arch::ArchOperand::X86Operand(op) = op {
-                                    if let arch::x86::X86OperandType::Imm(x) = op.op_type {
-                                        return Some(BranchInst::Jump(x as u64));
-                                    }
-                                }
-                            }
-                            Arch::Arm => {
-                                if let arch::ArchOperand::ArmOperand(op) = op {
-                                    if let arch::arm::ArmOperandType::Imm(x) = op.op_type {
-                                        return Some(BranchInst::Jump(x as u64));
-                                    }
-                                }
-                            }
-                            Arch::Arm64 => {
-                                if let arch::ArchOperand::Arm64Operand(op) = op {
-                                    if let arch::arm64::Arm64OperandType::Imm(x) = op.op_type {
-                                        return Some(BranchInst::Jump(x as u64));
-                                    }
-                                }
-                            }
-                            _ => todo!(),
-                        }
-                    }
-                }
-                _ => {}
-            }
+    // C string at addr, if bytes there look like one
+    fn string_at(bin: &dyn Binary, addr: u64) -> Option<String> {
+        let bytes = bin.bytes_at(addr)?;
+        let len = bytes.iter().take(MAX_STRING).position(|b| *b == 0)?;
+        let s = std::str::from_utf8(&bytes[..len]).ok()?;
+
+        if len < 2
+            || s.chars()
+                .any(|c| c.is_control() && !c.is_ascii_whitespace())
+        {
+            return None;
         }
 
-        None
+        Some(format!("{:?}", s))
     }
 
-    fn inst_to_string(c: &Capstone, elf: &Elf, inst: &Insn, arch: Arch) -> Text<'static> {
-        let detail = c.insn_detail(inst);
-        let line = if let Ok(d) = detail {
-            let group = d.groups();
-            let mut call_name = None;
-
-            for i in group {
-                match i {
-                    InsnGroupId(CALL_INST) => {
-                        for op in d.arch_detail().operands() {
-                            match arch {
-                                Arch::X86_64 | Arch::X86 => {
-                                    if let arch::ArchOperand::X86Operand(op) = op {
-                                        if let arch::x86::X86OperandType::Imm(x) = op.op_type {
-                                            call_name = elf.function_by_addr(x as u64);
-                                        }
-                                    }
-                                }
-                                Arch::Arm => {
-                                    if let arch::ArchOperand::ArmOperand(op) = op {
-                                        if let arch::arm::ArmOperandType::Imm(x) = op.op_type {
-                                            call_name = elf.function_by_addr(x as u64);
-                                        }
-                                    }
-                                }
-                                Arch::Arm64 => {
-                                    if let arch::ArchOperand::Arm64Operand(op) = op {
-                                        if let arch::arm64::Arm64OperandType::Imm(x) = op.op_type {
-                                            call_name = elf.function_by_addr(x as u64);
-                                        }
-                                    }
-                                }
-                                _ => todo!(),
-                            }
-                        }
-                    }
-                    InsnGroupId(JUMP_INST) => {
-                        // log_info!("Found jump inst at addr {:x}", inst.address());
-                    }
-                    _ => {}
-                }
-            }
+    fn describe_addr(bin: &dyn Binary, addr: u64) -> Option<String> {
+        if let Some(import) = bin.import_by_got(addr) {
+            return Some(format!("<{}@{}>", import.name, import.table));
+        }
 
-            if let Some(call) = call_name {
-                let mut text = Self::format_insn(inst);
+        if let Some(s) = Self::string_at(bin, addr) {
+            return Some(s);
+        }
 
+        // Anonymous data is at least told by the section it is in
+        let (name, off) = bin.symbol_at(addr).or_else(|| {
+            bin.sections()
+                .into_iter()
+                .find(|s| !s.name.is_empty() && s.addr <= addr && addr < s.addr + s.size)
+                .map(|s| (s.name, addr - s.addr))
+        })?;
+
+        match off {
+            0 => Some(format!("<{}>", name)),
+            off => Some(format!("<{}+0x{:x}>", name, off)),
+        }
+    }
+
+    fn inst_to_string(bin: &dyn Binary, inst: &Inst) -> Text<'static> {
+        let call_name = match inst.branch {
+            Some(BranchInst::Call(x)) => bin.function_by_addr(x),
+            _ => None,
+        };
+
+        let mut text = Self::format_insn(inst);
+
+        if let Some(r) = analysis::reloc(bin, inst) {
+            text.push(Span::from(format!("      <{}> ({})", r.name, r.kind)));
+        } else {
+            if let Some(call) = call_name {
                 text.push(Span::from(format!("      <{}>", call.name())));
-                Line::from(text)
-            } else {
-                Line::from(Self::format_insn(inst))
             }
+
+            if let Some(data) = inst.target.and_then(|t| Self::describe_addr(bin, t)) {
+                text.push(Span::from(format!("      {}", data)));
+            }
+        }
+
+        if !inst.cases.is_empty() {
+            let shown: Vec<_> = inst
+                .cases
+                .iter()
+                .take(MAX_CASES_SHOWN)
+                .map(|c| format!("0x{:x}", c))
+                .collect();
+            let more = if inst.cases.len() > MAX_CASES_SHOWN {
+                " ..."
+            } else {
+                ""
+            };
+
+            text.push(Span::from(format!(
+                "      cases: {}{}",
+                shown.join(" "),
+                more
+            )));
+        }
+
+        if inst.delay_slot {
+            let style = Style::default().fg(Color::DarkGray);
+            text.push(Span::styled("      ; delay slot", style));
+        }
+
+        Text::from(Line::from(text))
+    }
+
+    // Every jump gets a lane it alone occupies between its ends, shorter
+    // ones closer to instructions. Lanes are drawn outermost first
+    fn jump_gutter(bin: &dyn Binary, insts: &[Inst]) -> (Vec<Jump>, Gutter) {
+        let index: HashMap<u64, usize> =
+            insts.iter().enumerate().map(|(i, x)| (x.addr, i)).collect();
+        let mut all: Vec<Jump> = insts
+            .iter()
+            .enumerate()
+            .filter_map(|(i, inst)| {
+                Some(Jump {
+                    from: i,
+                    to: *index.get(&analysis::jump_target(bin, inst)?)?,
+                    lane: 0,
+                    column: 0,
+                })
+            })
+            .collect();
+
+        all.sort_by_key(|j| j.from.abs_diff(j.to));
+
+        let mut used: Vec<Vec<(usize, usize)>> = Vec::new();
+        let mut jumps = Vec::new();
+
+        for mut j in all {
+            let (a, b) = (j.from.min(j.to), j.from.max(j.to));
+            let free = |l: &Vec<(usize, usize)>| l.iter().all(|(x, y)| b < *x || *y < a);
+
+            j.lane = match used.iter().position(free) {
+                Some(l) => l,
+                None if used.len() < MAX_LANES => {
+                    used.push(Vec::new());
+                    used.len() - 1
+                }
+                None => continue,
+            };
+
+            used[j.lane].push((a, b));
+            jumps.push(j);
+        }
+
+        let width = if used.is_empty() {
+            0
         } else {
-            Line::from(Self::format_insn(inst))
+            used.len() * 2 + 2
         };
+        let mut mask = vec![vec![0u8; width]; insts.len()];
+        let mut owner = vec![vec![None; width]; insts.len()];
 
-        Text::from(line)
-    }
+        for (k, j) in jumps.iter_mut().enumerate() {
+            let (a, b) = (j.from.min(j.to), j.from.max(j.to));
+            j.column = (used.len() - 1 - j.lane) * 2;
+
+            for r in a..=b {
+                let ends = r == a || r == b;
+
+                if r > a {
+                    mask[r][j.column] |= UP;
+                }
+                if r < b {
+                    mask[r][j.column] |= DOWN;
+                }
+                if ends {
+                    mask[r][j.column] |= RIGHT;
+                }
+                owner[r][j.column] = Some(k);
 
-    fn cleanup_jump(&mut self) {
-        if let Some(range) = self.range_cleanup.take() {
-            for i in range.0 {
-                let text = self.string_list[i].lines[0].spans[0].content.to_mut();
-
-                for j in range.1 + 5..text.len() {
-                    let c = unsafe { &mut text.as_bytes_mut()[j] };
-                    if *c != b'-' && *c != b'|' && *c != b' ' && *c != b'>' {
-                        break;
-                    } else {
-                        *c = b' ';
+                if !ends {
+                    continue;
+                }
+
+                for c in j.column + 1..width {
+                    mask[r][c] |= LEFT;
+                    if c + 1 < width {
+                        mask[r][c] |= RIGHT;
                     }
+                    owner[r][c] = Some(k);
                 }
             }
         }
-    }
 
-    fn draw_jump(&mut self, state: &ListState) {
-        let idx = state.selected().unwrap();
+        let mut gutter: Gutter = mask
+            .iter()
+            .zip(owner.iter())
+            .map(|(m, o)| {
+                m.iter()
+                    .map(|m| line_char(*m))
+                    .zip(o.iter().copied())
+                    .collect()
+            })
+            .collect();
+
+        for j in &jumps {
+            gutter[j.to][width - 1].0 = '>';
+        }
 
-        if let Some(inst) = self.is_branch_inst(&self.insn_list.as_ref()[idx]) {
-            match inst {
-                BranchInst::Jump(addr) => {
-                    // Check that jump belongs to current function
-                    if self.insn_list.as_ref().iter().last().unwrap().address() < addr {
-                        return;
-                    }
+        (jumps, gutter)
+    }
 
-                    let self_addr = self.insn_list[idx].address();
-                    let text = unsafe {
-                        self.string_list[idx].lines[0].spans[0]
-                            .content
-                            .to_mut()
-                            .as_bytes_mut()
-                    };
-                    let addr_offset = text.iter().position(|c| *c == b' ').unwrap();
-
-                    if self.insn_list[idx].address() != addr {
-                        for j in addr_offset + 5..text.len() {
-                            if text[j] != b' ' {
-                                break;
-                            }
-
-                            text[j] = b'-';
-                        }
-                    }
+    // Header starts loop, latch jumps back to it, other blocks of body are
+    // only counted into depth
+    fn loop_gutter(blocks: &[Block], len: usize) -> Vec<(usize, char)> {
+        let loops = cfg::loops(blocks);
+        let mut rows = vec![(0, ' '); len];
 
-                    for i in if addr < self_addr {
-                        Either::Left((idx - 1..=0).rev())
-                    } else {
-                        Either::Right(idx + 1..self.string_list.len())
-                    } {
-                        let text = unsafe {
-                            self.string_list[i].lines[0].spans[0]
-                                .content
-                                .to_mut()
-                                .as_bytes_mut()
-                        };
-
-                        if self.insn_list[i].address() == addr {
-                            for j in addr_offset + 5..text.len() {
-                                if j == text.len() - 1 {
-                                    text[j] = b'>';
-                                    self.range_cleanup = Some(if addr < self_addr {
-                                        (
-                                            Range {
-                                                start: i,
-                                                end: idx + 1,
-                                            },
-                                            addr_offset,
-                                        )
-                                    } else {
-                                        (
-                                            Range {
-                                                start: idx,
-                                                end: i + 1,
-                                            },
-                                            addr_offset,
-                                        )
-                                    });
-                                } else {
-                                    text[j] = b'-';
-                                }
-                            }
-
-                            break;
-                        }
-
-                        text[addr_offset + 5] = b'|';
-                    }
+        if loops.is_empty() {
+            return Vec::new();
+        }
+
+        for l in &loops {
+            for b in &l.body {
+                for row in &mut rows[blocks[*b].start..blocks[*b].end] {
+                    row.0 += 1;
+                    row.1 = '│';
                 }
-                _ => {}
             }
         }
+
+        for l in &loops {
+            for b in &l.latches {
+                rows[blocks[*b].end - 1].1 = '└';
+            }
+        }
+
+        for l in &loops {
+            let r = blocks[l.header].start;
+
+            rows[r].1 = match rows[r].1 {
+                '└' if l.latches.contains(&l.header) => '↺',
+                _ => '┌',
+            };
+        }
+
+        rows
+    }
+
+    fn gutter_spans(&self, row: usize) -> Vec<Span<'static>> {
+        let mut spans = Vec::new();
+
+        if let Some((depth, mark)) = self.loops.get(row) {
+            let text = match depth {
+                0 => "    ".to_owned(),
+                d => format!("{:>2}{} ", d, mark),
+            };
+            let style = match depth {
+                0 => Style::default(),
+                d => JUMP_COLORS[(d - 1) % JUMP_COLORS.len()],
+            };
+
+            spans.push(Span::styled(text, style));
+        }
+
+        let mut text = String::new();
+        let mut style = None;
+        let highlight = self.highlight.map(|h| &self.jumps[h]);
+
+        for (c, (ch, owner)) in self.gutter[row].iter().enumerate() {
+            let s = match (highlight, owner) {
+                (Some(h), _) if h.covers(row, c) => *JUMP_SELECTED,
+                (_, Some(o)) => JUMP_COLORS[self.jumps[*o].lane % JUMP_COLORS.len()],
+                _ => Style::default(),
+            };
+
+            if style != Some(s) && !text.is_empty() {
+                spans.push(Span::styled(std::mem::take(&mut text), style.unwrap()));
+            }
+
+            style = Some(s);
+            text.push(*ch);
+        }
+
+        if let Some(style) = style {
+            spans.push(Span::styled(text, style));
+        }
+
+        spans.push(Span::from(" "));
+        spans
     }
 
     fn clean_debug(&mut self) {
@@ -364,7 +512,7 @@ impl FuncAsm {
 
     fn color_debug(&mut self, state: &ListState) -> Option<()> {
         let di = self.elf_debug_info.as_ref()?;
-        let addr = self.insn_list[state.selected().unwrap()].address();
+        let addr = self.insn_list[state.selected().unwrap()].addr;
 
         let line_orig = di.line_by_addr(addr)?;
         let line = line_orig - self.debug_info.as_ref()?.1;
@@ -378,7 +526,7 @@ impl FuncAsm {
             let addrs = di.line_to_addrs(*line_orig);
             for i in addrs.unwrap() {
                 for (cnt, j) in &mut self.insn_list.iter().enumerate() {
-                    if i.contains(&j.address()) {
+                    if i.contains(&j.addr) {
                         self.string_list[cnt].patch_style(STYLE_ARRAY[0]);
                         self.marked.1.push(cnt);
                     }
@@ -392,15 +540,22 @@ impl FuncAsm {
 
 impl ScreenItem for FuncAsm {
     fn title(&self) -> String {
-        format!("Disassembly of {}", self.name)
+        format!("Disassembly of {}", self.func.name())
     }
 
-    fn draw(&self) -> List {
+    fn draw(&self) -> List<'_> {
         let list = List::new(
             self.string_list
-                .clone()
-                .into_iter()
-                .map(ListItem::new)
+                .iter()
+                .enumerate()
+                .map(|(i, t)| {
+                    let mut t = t.clone();
+
+                    // Gutter goes between address and instruction
+                    let gutter = self.gutter_spans(i);
+                    t.lines[0].spans.splice(1..1, gutter);
+                    ListItem::new(t)
+                })
                 .collect::<Vec<ListItem>>(),
         )
         .style(Style::default().fg(Color::White))
@@ -416,12 +571,16 @@ impl ScreenItem for FuncAsm {
     fn cursor_move(&mut self, state: &ListState) {
         self.clean_debug();
         self.color_debug(state);
-        self.cleanup_jump();
-        self.draw_jump(state);
+        self.highlight = state.selected().and_then(|i| {
+            self.jumps
+                .iter()
+                .position(|j| j.from == i)
+                .or_else(|| self.jumps.iter().position(|j| j.to == i))
+        });
     }
 
-    fn second_frame(&self) -> Option<Paragraph> {
-        Some(Paragraph::new(if self.marked.0.len() == 0 {
+    fn second_frame(&self) -> Option<Paragraph<'_>> {
+        Some(Paragraph::new(if self.marked.0.is_empty() {
             self.debug_info.as_ref()?.0.clone()
         } else {
             let line = if self.marked.0[0] < 5 {
@@ -430,49 +589,161 @@ impl ScreenItem for FuncAsm {
                 self.marked.0[0] - 5
             };
 
-            (&self.debug_info.as_ref()?.0.clone()[line..]).to_vec()
+            self.debug_info.as_ref()?.0[line..].to_vec()
         }))
     }
 
     fn go_in(
         &mut self,
-        elf: &Elf,
-        cs: &'static Capstone,
+        bin: &dyn Binary,
+        dec: &'static Decoder,
         state: &mut ListState,
     ) -> Option<ItemType> {
         let idx = state.selected().unwrap();
-        self.cleanup_jump();
 
-        if let Some(inst) = self.is_branch_inst(&self.insn_list.as_ref()[idx]) {
+        let inst = &self.insn_list[idx];
+        if let Some(r) = analysis::reloc(bin, inst) {
+            let f = bin.function_by_addr(r.target?)?;
+
+            return Some(ItemType::FunctionDisas(FuncAsm::new(f, bin, dec)));
+        }
+
+        if !inst.cases.is_empty() {
+            return Some(ItemType::CaseList(CaseList::new(
+                inst.addr,
+                inst.cases.clone(),
+            )));
+        }
+
+        if let Some(inst) = self.insn_list[idx].branch {
             match inst {
                 BranchInst::Call(addr) => {
-                    let call_name = elf.function_by_addr(addr)?;
-                    Some(ItemType::FunctionDisas(FuncAsm::new(call_name, elf, cs)))
+                    if let Some(import) = bin.import_by_addr(addr) {
+                        return Some(ItemType::ImportInfo(ImportInfo::new(import)));
+                    }
+
+                    let call_name = bin.function_by_addr(addr)?;
+                    Some(ItemType::FunctionDisas(FuncAsm::new(call_name, bin, dec)))
+                }
+                // Local definition wins, as dynamic linker would bind it the
+                // same way unless symbol is preempted
+                BranchInst::CallVia(slot) | BranchInst::JumpVia(slot) => {
+                    let import = bin.import_by_got(slot)?;
+
+                    if let Some(f) = import.local.and_then(|a| bin.function_by_addr(a)) {
+                        return Some(ItemType::FunctionDisas(FuncAsm::new(f, bin, dec)));
+                    }
+
+                    Some(ItemType::ImportInfo(ImportInfo::new(import)))
                 }
                 BranchInst::Jump(addr) => {
-                    let self_addr = self.insn_list[idx].address();
-
-                    if addr < self_addr {
-                        for i in (idx..=0).rev() {
-                            if self.insn_list[i].address() == addr {
-                                state.select(Some(i));
-                                break;
-                            }
-                        }
-                    } else {
-                        for i in idx..self.insn_list.len() {
-                            if self.insn_list[i].address() == addr {
-                                state.select(Some(i));
-                                break;
-                            }
-                        }
+                    // Tail call
+                    if !self.insn_list.iter().any(|i| i.addr == addr) {
+                        let f = bin.function_by_addr(addr)?;
+                        return Some(ItemType::FunctionDisas(FuncAsm::new(f, bin, dec)));
                     }
 
+                    self.select_addr(addr, state);
                     None
                 }
+                BranchInst::Return => None,
             }
         } else {
             None
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::{Arch, Section};
+
+    // String literal at the start of .data
+    struct Data;
+
+    impl Binary for Data {
+        fn arch(&self) -> Arch {
+            Arch::X86_64
+        }
+
+        fn function_by_addr(&self, _addr: u64) -> Option<Function> {
+            None
+        }
+
+        fn function_names(&self) -> Vec<Function> {
+            Vec::new()
+        }
+
+        fn sections(&self) -> Vec<Section> {
+            vec![Section {
+                name: ".data".to_owned(),
+                addr: 0x2000,
+                size: 0x100,
+            }]
+        }
+
+        fn func_code(&self, _f: &Function) -> (&[u8], u64) {
+            (&[], 0)
+        }
+
+        fn bytes_at(&self, addr: u64) -> Option<&[u8]> {
+            let data: &[u8] = b"Hello\n\0\x01\x02";
+
+            data.get(addr.checked_sub(0x2000)? as usize..)
+        }
+    }
+
+    #[test]
+    fn describe_data_targets() {
+        assert_eq!(
+            FuncAsm::describe_addr(&Data, 0x2000).as_deref(),
+            Some("\"Hello\\n\"")
+        );
+        assert_eq!(
+            FuncAsm::describe_addr(&Data, 0x2007).as_deref(),
+            Some("<.data+0x7>")
+        );
+        assert_eq!(FuncAsm::describe_addr(&Data, 0x3000), None);
+    }
+
+    fn inst(addr: u64, branch: Option<BranchInst>) -> Inst {
+        Inst {
+            addr,
+            bytes: vec![0x90],
+            mnemonic: "nop".to_owned(),
+            op_str: String::new(),
+            branch,
+            target: None,
+            cases: Vec::new(),
+            delay_slot: false,
+        }
+    }
+
+    #[test]
+    fn jump_lanes() {
+        let insts = [
+            inst(0, None),
+            inst(1, Some(BranchInst::Jump(4))),
+            inst(2, None),
+            inst(3, Some(BranchInst::Jump(2))),
+            inst(4, None),
+            // Jumps out of function are not drawn
+            inst(5, Some(BranchInst::Jump(0x100))),
+        ];
+        let (jumps, gutter) = FuncAsm::jump_gutter(&Data, &insts);
+        let rows: Vec<String> = gutter
+            .iter()
+            .map(|r| r.iter().map(|c| c.0).collect())
+            .collect();
+
+        // Shorter jump is closer to instructions
+        assert_eq!(jumps.len(), 2);
+        assert_eq!((jumps[0].from, jumps[0].lane), (3, 0));
+        assert_eq!((jumps[1].from, jumps[1].lane), (1, 1));
+        assert_eq!(
+            rows,
+            ["      ", "┌─────", "│ ┌──>", "│ └───", "└────>", "      "]
+        );
+    }
+}
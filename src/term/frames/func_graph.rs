@@ -0,0 +1,496 @@
+use super::{ItemType, ScreenItem};
+use crate::binary::Binary;
+use crate::cfg::{Block, EdgeKind};
+use crate::decoder::{Decoder, Inst};
+use crate::term::events::KeyboardEvent;
+use tui::{
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{List, ListItem, ListState},
+};
+
+// Longer instructions are cut in boxes
+const MAX_LINE: usize = 48;
+// Columns between boxes of one layer
+const GAP: usize = 4;
+
+pub const UP: u8 = 1;
+pub const DOWN: u8 = 2;
+pub const LEFT: u8 = 4;
+pub const RIGHT: u8 = 8;
+
+#[derive(Clone, Copy)]
+struct Cell {
+    ch: char,
+    style: Style,
+}
+
+struct Placed {
+    x: usize,
+    y: usize,
+    w: usize,
+    h: usize,
+    layer: usize,
+}
+
+// Basic blocks of a function drawn as boxes, layers of them go down from the
+// entry. Enter goes back to the listing at the selected block, done by App
+pub struct FuncGraph {
+    name: String,
+    starts: Vec<u64>,
+    lines: Vec<Vec<String>>,
+    boxes: Vec<Placed>,
+    layers: Vec<Vec<usize>>,
+    // Edges drawn once, boxes are drawn over them with selection
+    edges: Vec<Vec<Cell>>,
+    exits: Vec<(usize, usize, Style)>,
+    selected: usize,
+    scroll: usize,
+    // Columns of graph seen at once, graph is scrolled sideways to keep
+    // selected block in them
+    width: usize,
+    ui_list: Vec<ListItem<'static>>,
+}
+
+fn edge_style(kind: EdgeKind) -> Style {
+    match kind {
+        EdgeKind::True => Style::default().fg(Color::Green),
+        EdgeKind::False => Style::default().fg(Color::Red),
+        EdgeKind::Unconditional => Style::default().fg(Color::Blue),
+    }
+}
+
+// Box drawing character joining the sides set in mask
+pub fn line_char(mask: u8) -> char {
+    match mask {
+        0 => ' ',
+        m if m == LEFT | RIGHT || m == LEFT || m == RIGHT => '─',
+        m if m == DOWN | RIGHT => '┌',
+        m if m == DOWN | LEFT => '┐',
+        m if m == UP | RIGHT => '└',
+        m if m == UP | LEFT => '┘',
+        m if m == UP | DOWN | RIGHT => '├',
+        m if m == UP | DOWN | LEFT => '┤',
+        m if m == LEFT | RIGHT | DOWN => '┬',
+        m if m == LEFT | RIGHT | UP => '┴',
+        m if m == UP | DOWN | LEFT | RIGHT => '┼',
+        _ => '│',
+    }
+}
+
+// Blocks are put into layers by longest path from entry, ignoring edges going
+// back to a block that is still being visited
+fn layers(blocks: &[Block]) -> Vec<usize> {
+    let n = blocks.len();
+    let mut state = vec![0u8; n]; // 0 new, 1 on stack, 2 done
+    let mut back = vec![Vec::new(); n];
+    let mut order = Vec::new();
+
+    for root in 0..n {
+        if state[root] != 0 {
+            continue;
+        }
+
+        let mut stack = vec![(root, 0)];
+        state[root] = 1;
+
+        while let Some((b, i)) = stack.pop() {
+            match blocks[b].succs.get(i) {
+                Some((s, _)) => {
+                    stack.push((b, i + 1));
+
+                    match state[*s] {
+                        0 => {
+                            state[*s] = 1;
+                            stack.push((*s, 0));
+                        }
+                        1 => back[b].push(*s),
+                        _ => {}
+                    }
+                }
+                None => {
+                    state[b] = 2;
+                    order.push(b);
+                }
+            }
+        }
+    }
+
+    let mut layer = vec![0; n];
+
+    for b in order.into_iter().rev() {
+        for (s, _) in &blocks[b].succs {
+            if !back[b].contains(s) {
+                layer[*s] = layer[*s].max(layer[b] + 1);
+            }
+        }
+    }
+
+    layer
+}
+
+impl FuncGraph {
+    pub fn new(name: String, insts: &[Inst], blocks: &[Block], selected: usize) -> Self {
+        let lines: Vec<Vec<String>> = blocks
+            .iter()
+            .map(|b| {
+                insts[b.start..b.end]
+                    .iter()
+                    .map(|i| {
+                        format!("0x{:x}  {} {}", i.addr, i.mnemonic, i.op_str)
+                            .chars()
+                            .take(MAX_LINE)
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let layer_of = layers(blocks);
+        let count = layer_of.iter().max().map_or(0, |l| l + 1);
+        let mut layers = vec![Vec::new(); count];
+
+        for (b, l) in layer_of.iter().enumerate() {
+            layers[*l].push(b);
+        }
+
+        let mut boxes: Vec<Placed> = lines
+            .iter()
+            .zip(layer_of.iter())
+            .map(|(l, layer)| Placed {
+                x: 0,
+                y: 0,
+                w: l.iter().map(|s| s.chars().count()).max().unwrap_or(0) + 4,
+                h: l.len() + 2,
+                layer: *layer,
+            })
+            .collect();
+
+        // Edges between neighbouring layers go straight down, others take a
+        // lane right of all boxes. Each edge gets its own row in the channel
+        // below source layer, long ones one more above destination layer
+        let edges: Vec<(usize, usize, EdgeKind, usize)> = blocks
+            .iter()
+            .enumerate()
+            .flat_map(|(b, block)| {
+                block
+                    .succs
+                    .iter()
+                    .enumerate()
+                    .map(move |(k, (s, kind))| (b, *s, *kind, k))
+            })
+            .collect();
+        let long = |e: &(usize, usize, EdgeKind, usize)| layer_of[e.1] != layer_of[e.0] + 1;
+
+        let mut out_rows = vec![0; count];
+        let mut in_rows = vec![0; count];
+        let mut rows = Vec::new();
+
+        for e in &edges {
+            let out = out_rows[layer_of[e.0]];
+            out_rows[layer_of[e.0]] += 1;
+
+            let into = if long(e) {
+                in_rows[layer_of[e.1]] += 1;
+                Some(in_rows[layer_of[e.1]] - 1)
+            } else {
+                None
+            };
+
+            rows.push((out, into));
+        }
+
+        // Channel above layer holds rows for long edges into it, rows for
+        // edges out of the previous layer and the arrow row
+        let widths: Vec<usize> = layers
+            .iter()
+            .map(|l| l.iter().map(|b| boxes[*b].w + GAP).sum::<usize>())
+            .collect();
+        let width = widths.iter().copied().max().unwrap_or(0);
+        let mut tops = Vec::new();
+        let mut y = 0;
+
+        for (l, blocks) in layers.iter().enumerate() {
+            y += in_rows[l] + if l == 0 { 0 } else { out_rows[l - 1] } + 1;
+            tops.push(y);
+
+            let mut x = (width - widths[l]) / 2;
+            for b in blocks {
+                boxes[*b].x = x;
+                boxes[*b].y = y;
+                x += boxes[*b].w + GAP;
+            }
+
+            y += blocks.iter().map(|b| boxes[*b].h).max().unwrap_or(0);
+        }
+
+        let bottoms: Vec<usize> = layers
+            .iter()
+            .enumerate()
+            .map(|(l, b)| tops[l] + b.iter().map(|b| boxes[*b].h).max().unwrap_or(0))
+            .collect();
+        let height = y + count.checked_sub(1).map_or(0, |l| out_rows[l]) + 1;
+        let lanes = edges.iter().filter(|e| long(e)).count();
+        let mut mask = vec![vec![0u8; width + 2 * lanes + 2]; height];
+        let mut styles = vec![vec![Style::default(); width + 2 * lanes + 2]; height];
+        let mut arrows = Vec::new();
+        let mut exits = Vec::new();
+        let mut lane = width + 1;
+
+        let mut line = |from: (usize, usize), to: (usize, usize), style: Style| {
+            let (x0, y0, x1, y1) = (from.0, from.1, to.0, to.1);
+
+            if x0 == x1 {
+                for y in y0.min(y1)..=y0.max(y1) {
+                    if y > y0.min(y1) {
+                        mask[y][x0] |= UP;
+                    }
+                    if y < y0.max(y1) {
+                        mask[y][x0] |= DOWN;
+                    }
+                    styles[y][x0] = style;
+                }
+            } else {
+                for x in x0.min(x1)..=x0.max(x1) {
+                    if x > x0.min(x1) {
+                        mask[y0][x] |= LEFT;
+                    }
+                    if x < x0.max(x1) {
+                        mask[y0][x] |= RIGHT;
+                    }
+                    styles[y0][x] = style;
+                }
+            }
+        };
+
+        for (e, (out, into)) in edges.iter().zip(rows.iter()) {
+            let (src, dst) = (&boxes[e.0], &boxes[e.1]);
+            let n = blocks[e.0].succs.len();
+            let style = edge_style(e.2);
+            let sx = src.x + (e.3 + 1) * src.w / (n + 1);
+            let dx = dst.x + dst.w / 2;
+            let r1 = bottoms[src.layer] + out;
+            let arrow = dst.y - 1;
+
+            line((sx, src.y + src.h - 1), (sx, r1), style);
+
+            match into {
+                None => {
+                    line((sx, r1), (dx, r1), style);
+                    line((dx, r1), (dx, arrow), style);
+                }
+                Some(into) => {
+                    let r2 = arrow - in_rows[dst.layer] + into;
+
+                    line((sx, r1), (lane, r1), style);
+                    line((lane, r1), (lane, r2), style);
+                    line((lane, r2), (dx, r2), style);
+                    line((dx, r2), (dx, arrow), style);
+                    lane += 2;
+                }
+            }
+
+            exits.push((sx, src.y + src.h - 1, style));
+            arrows.push((dx, arrow, style));
+        }
+
+        let mut edges: Vec<Vec<Cell>> = mask
+            .iter()
+            .zip(styles.iter())
+            .map(|(m, s)| {
+                m.iter()
+                    .zip(s.iter())
+                    .map(|(m, s)| Cell {
+                        ch: line_char(*m),
+                        style: *s,
+                    })
+                    .collect()
+            })
+            .collect();
+
+        for (x, y, style) in arrows {
+            edges[y][x] = Cell { ch: '▼', style };
+        }
+
+        let mut s = Self {
+            name,
+            starts: blocks.iter().map(|b| insts[b.start].addr).collect(),
+            lines,
+            boxes,
+            layers,
+            edges,
+            exits,
+            selected,
+            scroll: 0,
+            width: 0,
+            ui_list: Vec::new(),
+        };
+
+        s.render();
+        s
+    }
+
+    // Frame state showing selected block
+    pub fn state(&self) -> ListState {
+        let p = &self.boxes[self.selected];
+
+        ListState::default().with_selected(Some(p.y + p.h - 1))
+    }
+
+    // Address of the first instruction of selected block
+    pub fn addr(&self) -> Option<u64> {
+        self.starts.get(self.selected).copied()
+    }
+
+    fn render(&mut self) {
+        let mut canvas = self.edges.clone();
+
+        for (b, p) in self.boxes.iter().enumerate() {
+            let style = if b == self.selected {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default().fg(Color::White)
+            };
+            let mut put = |x: usize, y: usize, ch: char, style: Style| {
+                canvas[y][x] = Cell { ch, style };
+            };
+
+            for x in p.x..p.x + p.w {
+                put(x, p.y, '─', style);
+                put(x, p.y + p.h - 1, '─', style);
+            }
+
+            for y in p.y..p.y + p.h {
+                put(p.x, y, '│', style);
+                put(p.x + p.w - 1, y, '│', style);
+            }
+
+            put(p.x, p.y, '┌', style);
+            put(p.x + p.w - 1, p.y, '┐', style);
+            put(p.x, p.y + p.h - 1, '└', style);
+            put(p.x + p.w - 1, p.y + p.h - 1, '┘', style);
+
+            for (i, l) in self.lines[b].iter().enumerate() {
+                for (j, ch) in l.chars().enumerate() {
+                    put(p.x + 2 + j, p.y + 1 + i, ch, Style::default());
+                }
+            }
+        }
+
+        for (x, y, style) in &self.exits {
+            canvas[*y][*x] = Cell {
+                ch: '┬',
+                style: *style,
+            };
+        }
+
+        self.ui_list = canvas
+            .iter()
+            .map(|row| {
+                let mut spans: Vec<Span> = Vec::new();
+                let mut text = String::new();
+                let mut style = None;
+
+                for c in row.iter().skip(self.scroll) {
+                    if style != Some(c.style) && !text.is_empty() {
+                        spans.push(Span::styled(std::mem::take(&mut text), style.unwrap()));
+                    }
+
+                    style = Some(c.style);
+                    text.push(c.ch);
+                }
+
+                if let Some(style) = style {
+                    spans.push(Span::styled(text, style));
+                }
+
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+    }
+
+    fn select(&mut self, b: usize, state: &mut ListState) {
+        let (old, new) = (&self.boxes[self.selected], &self.boxes[b]);
+
+        // Whole box is shown when going down, arrows into it when going up
+        let row = if new.y > old.y {
+            new.y + new.h - 1
+        } else {
+            new.y.saturating_sub(2)
+        };
+
+        if new.x + new.w > self.scroll + self.width || new.x < self.scroll {
+            self.scroll = new.x.saturating_sub(GAP);
+        }
+
+        self.selected = b;
+        state.select(Some(row));
+        self.render();
+    }
+
+    // Closest block of another layer, measured between box centers
+    fn nearest(&self, layer: usize) -> Option<usize> {
+        let p = &self.boxes[self.selected];
+        let center = |b: &Placed| (b.x + b.w / 2) as i64;
+
+        self.layers
+            .get(layer)?
+            .iter()
+            .min_by_key(|b| (center(&self.boxes[**b]) - center(p)).abs())
+            .copied()
+    }
+
+    // Moves between blocks, returns whether event was one of that
+    pub fn navigate(&mut self, e: &KeyboardEvent, state: &mut ListState) -> bool {
+        let layer = self.boxes[self.selected].layer;
+        let pos = self.layers[layer].iter().position(|b| *b == self.selected);
+
+        let to = match e {
+            KeyboardEvent::Next => self.nearest(layer + 1),
+            KeyboardEvent::Prev => layer.checked_sub(1).and_then(|l| self.nearest(l)),
+            KeyboardEvent::Left => pos
+                .and_then(|p| p.checked_sub(1))
+                .map(|p| self.layers[layer][p]),
+            KeyboardEvent::Right => pos.and_then(|p| self.layers[layer].get(p + 1)).copied(),
+            _ => return false,
+        };
+
+        match to {
+            Some(b) => self.select(b, state),
+            // Nothing left of the first box, App leaves the graph instead
+            None if matches!(e, KeyboardEvent::Left) => return false,
+            None => {}
+        }
+
+        true
+    }
+}
+
+impl ScreenItem for FuncGraph {
+    fn title(&self) -> String {
+        format!("Graph of {}", self.name)
+    }
+
+    fn draw(&self) -> List<'_> {
+        List::new(self.ui_list.clone())
+    }
+
+    fn list_size(&self) -> usize {
+        self.ui_list.len()
+    }
+
+    fn go_in(
+        &mut self,
+        _bin: &dyn Binary,
+        _dec: &'static Decoder,
+        _state: &mut ListState,
+    ) -> Option<ItemType> {
+        None
+    }
+
+    // Borders take a column on each side
+    fn resize(&mut self, area: Rect) {
+        self.width = area.width.saturating_sub(2) as usize;
+    }
+}
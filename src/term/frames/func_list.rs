@@ -1,7 +1,7 @@
 use super::func_asm::FuncAsm;
 use super::{ItemType, ScreenItem};
-use crate::elf::{Elf, Function};
-use capstone::Capstone;
+use crate::binary::{Binary, Function};
+use crate::decoder::Decoder;
 use tui::{
     style::{Color, Style},
     widgets::{List, ListItem, ListState},
@@ -27,11 +27,18 @@ impl FuncList {
         Self {
             ui_list: func_list
                 .iter()
-                .map(|i| ListItem::new(i.1.name().clone()))
+                .map(|i| match i.1.tag() {
+                    Some(tag) => ListItem::new(format!("{:<8}{}", tag, i.1.name())),
+                    None => ListItem::new(i.1.name().clone()),
+                })
                 .collect(),
             func_list: l,
         }
     }
+
+    pub fn function_at(&self, state: &ListState) -> Option<Function> {
+        self.func_list.get(state.selected()?).cloned()
+    }
 }
 
 impl ScreenItem for FuncList {
@@ -39,7 +46,7 @@ impl ScreenItem for FuncList {
         "Function list".to_owned()
     }
 
-    fn draw(&self) -> List {
+    fn draw(&self) -> List<'_> {
         let list = List::new(self.ui_list.clone())
             .style(Style::default().fg(Color::White))
             .highlight_style(Style::default().bg(Color::DarkGray));
@@ -53,15 +60,11 @@ impl ScreenItem for FuncList {
 
     fn go_in(
         &mut self,
-        elf: &Elf,
-        cs: &'static Capstone,
+        bin: &dyn Binary,
+        dec: &'static Decoder,
         state: &mut ListState,
     ) -> Option<ItemType> {
-        let new = FuncAsm::new(
-            self.func_list[state.selected().unwrap()].clone(),
-            elf,
-            cs,
-        );
+        let new = FuncAsm::new(self.func_list[state.selected().unwrap()].clone(), bin, dec);
 
         Some(ItemType::FunctionDisas(new))
     }
@@ -0,0 +1,80 @@
+use super::func_asm::FuncAsm;
+use super::{ItemType, ScreenItem};
+use crate::binary::{Binary, Import};
+use crate::decoder::Decoder;
+use tui::{
+    style::{Color, Style},
+    widgets::{List, ListItem, ListState},
+};
+
+// What is known about function from shared library. Enter opens its stub, if
+// there is one
+pub struct ImportInfo {
+    import: Import,
+    ui_list: Vec<ListItem<'static>>,
+}
+
+impl ImportInfo {
+    pub fn new(import: Import) -> Self {
+        let mut lines = vec![format!("{:<12}{}", "name", import.name)];
+
+        if let Some(stub) = &import.stub {
+            lines.push(format!(
+                "{:<12}0x{:x} ({})",
+                "stub",
+                stub.addr(),
+                import.section
+            ));
+        }
+
+        lines.push(format!(
+            "{:<12}0x{:x} ({})",
+            format!("{} slot", import.table),
+            import.got,
+            import.reloc
+        ));
+
+        if let Some(v) = &import.version {
+            lines.push(format!("{:<12}{}", "version", v));
+        }
+
+        match &import.library {
+            Some(l) => lines.push(format!("{:<12}{}", "library", l)),
+            None => lines.push(format!("{:<12}{}", "needed", import.needed.join(", "))),
+        }
+
+        Self {
+            ui_list: lines.into_iter().map(ListItem::new).collect(),
+            import,
+        }
+    }
+}
+
+impl ScreenItem for ImportInfo {
+    fn title(&self) -> String {
+        format!("Import {}", self.import.name)
+    }
+
+    fn draw(&self) -> List<'_> {
+        List::new(self.ui_list.clone())
+            .style(Style::default().fg(Color::White))
+            .highlight_style(Style::default().bg(Color::DarkGray))
+    }
+
+    fn list_size(&self) -> usize {
+        self.ui_list.len()
+    }
+
+    fn go_in(
+        &mut self,
+        bin: &dyn Binary,
+        dec: &'static Decoder,
+        _state: &mut ListState,
+    ) -> Option<ItemType> {
+        Some(ItemType::FunctionDisas(FuncAsm::new(
+            self.import.stub.clone()?,
+            bin,
+            dec,
+        )))
+    }
+}
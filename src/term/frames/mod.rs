@@ -1,28 +1,61 @@
-use crate::elf::Elf;
-use capstone::Capstone;
+use crate::binary::Binary;
+use crate::decoder::Decoder;
+use tui::layout::Rect;
 use tui::widgets::{List, ListState, Paragraph};
 
+pub mod archive_list;
+pub mod call_tree;
+pub mod case_list;
+pub mod error_info;
 pub mod func_asm;
+pub mod func_graph;
 pub mod func_list;
+pub mod import_info;
+pub mod slice_list;
+pub mod xref_list;
 
+use archive_list::ArchiveList;
+use call_tree::CallTree;
+use case_list::CaseList;
+use error_info::ErrorInfo;
 use func_asm::FuncAsm;
+use func_graph::FuncGraph;
 use func_list::FuncList;
+use import_info::ImportInfo;
+use slice_list::SliceList;
+use xref_list::XrefList;
 
 pub enum ItemType {
     FunctionList(FuncList),
     FunctionDisas(FuncAsm),
+    SliceList(SliceList),
+    ArchiveList(ArchiveList),
+    ImportInfo(ImportInfo),
+    ErrorInfo(ErrorInfo),
+    CaseList(CaseList),
+    XrefList(XrefList),
+    FunctionGraph(FuncGraph),
+    CallTree(CallTree),
 }
 
 impl ScreenItem for ItemType {
     fn go_in(
         &mut self,
-        elf: &Elf,
-        cs: &'static Capstone,
+        bin: &dyn Binary,
+        dec: &'static Decoder,
         state: &mut ListState,
     ) -> Option<ItemType> {
         match self {
-            Self::FunctionList(e) => e.go_in(elf, cs, state),
-            Self::FunctionDisas(e) => e.go_in(elf, cs, state),
+            Self::FunctionList(e) => e.go_in(bin, dec, state),
+            Self::FunctionDisas(e) => e.go_in(bin, dec, state),
+            Self::SliceList(e) => e.go_in(bin, dec, state),
+            Self::ArchiveList(e) => e.go_in(bin, dec, state),
+            Self::ImportInfo(e) => e.go_in(bin, dec, state),
+            Self::ErrorInfo(e) => e.go_in(bin, dec, state),
+            Self::CaseList(e) => e.go_in(bin, dec, state),
+            Self::XrefList(e) => e.go_in(bin, dec, state),
+            Self::FunctionGraph(e) => e.go_in(bin, dec, state),
+            Self::CallTree(e) => e.go_in(bin, dec, state),
         }
     }
 
@@ -30,13 +63,29 @@ impl ScreenItem for ItemType {
         match self {
             Self::FunctionList(s) => s.list_size(),
             Self::FunctionDisas(s) => s.list_size(),
+            Self::SliceList(s) => s.list_size(),
+            Self::ArchiveList(s) => s.list_size(),
+            Self::ImportInfo(s) => s.list_size(),
+            Self::ErrorInfo(s) => s.list_size(),
+            Self::CaseList(s) => s.list_size(),
+            Self::XrefList(s) => s.list_size(),
+            Self::FunctionGraph(s) => s.list_size(),
+            Self::CallTree(s) => s.list_size(),
         }
     }
 
-    fn draw(&self) -> List {
+    fn draw(&self) -> List<'_> {
         match self {
             Self::FunctionList(s) => s.draw(),
             Self::FunctionDisas(s) => s.draw(),
+            Self::SliceList(s) => s.draw(),
+            Self::ArchiveList(s) => s.draw(),
+            Self::ImportInfo(s) => s.draw(),
+            Self::ErrorInfo(s) => s.draw(),
+            Self::CaseList(s) => s.draw(),
+            Self::XrefList(s) => s.draw(),
+            Self::FunctionGraph(s) => s.draw(),
+            Self::CallTree(s) => s.draw(),
         }
     }
 
@@ -44,6 +93,29 @@ impl ScreenItem for ItemType {
         match self {
             Self::FunctionList(s) => s.find(state, ss),
             Self::FunctionDisas(s) => s.find(state, ss),
+            Self::SliceList(s) => s.find(state, ss),
+            Self::ArchiveList(s) => s.find(state, ss),
+            Self::ImportInfo(s) => s.find(state, ss),
+            Self::ErrorInfo(s) => s.find(state, ss),
+            Self::CaseList(s) => s.find(state, ss),
+            Self::XrefList(s) => s.find(state, ss),
+            Self::FunctionGraph(s) => s.find(state, ss),
+            Self::CallTree(s) => s.find(state, ss),
+        }
+    }
+
+    fn resize(&mut self, area: Rect) {
+        match self {
+            Self::FunctionList(s) => s.resize(area),
+            Self::FunctionDisas(s) => s.resize(area),
+            Self::SliceList(s) => s.resize(area),
+            Self::ArchiveList(s) => s.resize(area),
+            Self::ImportInfo(s) => s.resize(area),
+            Self::ErrorInfo(s) => s.resize(area),
+            Self::CaseList(s) => s.resize(area),
+            Self::XrefList(s) => s.resize(area),
+            Self::FunctionGraph(s) => s.resize(area),
+            Self::CallTree(s) => s.resize(area),
         }
     }
 
@@ -51,6 +123,14 @@ impl ScreenItem for ItemType {
         match self {
             Self::FunctionList(s) => s.cursor_move(state),
             Self::FunctionDisas(s) => s.cursor_move(state),
+            Self::SliceList(s) => s.cursor_move(state),
+            Self::ArchiveList(s) => s.cursor_move(state),
+            Self::ImportInfo(s) => s.cursor_move(state),
+            Self::ErrorInfo(s) => s.cursor_move(state),
+            Self::CaseList(s) => s.cursor_move(state),
+            Self::XrefList(s) => s.cursor_move(state),
+            Self::FunctionGraph(s) => s.cursor_move(state),
+            Self::CallTree(s) => s.cursor_move(state),
         }
     }
 
@@ -58,13 +138,29 @@ impl ScreenItem for ItemType {
         match self {
             Self::FunctionList(s) => s.title(),
             Self::FunctionDisas(s) => s.title(),
+            Self::SliceList(s) => s.title(),
+            Self::ArchiveList(s) => s.title(),
+            Self::ImportInfo(s) => s.title(),
+            Self::ErrorInfo(s) => s.title(),
+            Self::CaseList(s) => s.title(),
+            Self::XrefList(s) => s.title(),
+            Self::FunctionGraph(s) => s.title(),
+            Self::CallTree(s) => s.title(),
         }
     }
 
-    fn second_frame(&self) -> Option<Paragraph> {
+    fn second_frame(&self) -> Option<Paragraph<'_>> {
         match self {
             Self::FunctionList(s) => s.second_frame(),
             Self::FunctionDisas(s) => s.second_frame(),
+            Self::SliceList(s) => s.second_frame(),
+            Self::ArchiveList(s) => s.second_frame(),
+            Self::ImportInfo(s) => s.second_frame(),
+            Self::ErrorInfo(s) => s.second_frame(),
+            Self::CaseList(s) => s.second_frame(),
+            Self::XrefList(s) => s.second_frame(),
+            Self::FunctionGraph(s) => s.second_frame(),
+            Self::CallTree(s) => s.second_frame(),
         }
     }
 }
@@ -72,20 +168,23 @@ impl ScreenItem for ItemType {
 pub trait ScreenItem {
     fn title(&self) -> String;
     fn list_size(&self) -> usize;
-    fn draw(&self) -> List;
+    fn draw(&self) -> List<'_>;
     fn go_in(
         &mut self,
-        elf: &Elf,
-        cs: &'static Capstone,
+        bin: &dyn Binary,
+        dec: &'static Decoder,
         state: &mut ListState,
     ) -> Option<ItemType>;
 
-    fn second_frame(&self) -> Option<Paragraph> {
+    fn second_frame(&self) -> Option<Paragraph<'_>> {
         None
     }
 
     fn cursor_move(&mut self, _state: &ListState) {}
 
+    // Area list is drawn in, borders included
+    fn resize(&mut self, _area: Rect) {}
+
     fn find(&mut self, _state: &mut ListState, _s: &str) {
         crate::log_warn!("Unimplemented!");
     }
@@ -0,0 +1,63 @@
+use super::{ItemType, ScreenItem};
+use crate::binary::{self, Binary};
+use crate::debug_file::DebugSearch;
+use crate::decoder::Decoder;
+use crate::macho::FatSlice;
+use tui::{
+    style::{Color, Style},
+    widgets::{List, ListItem, ListState},
+};
+
+// Startup frame for universal binaries: pick an architecture to look at
+pub struct SliceList {
+    slices: Vec<FatSlice>,
+    // Separate debug info is looked up for whichever slice is picked
+    search: DebugSearch,
+    ui_list: Vec<ListItem<'static>>,
+}
+
+impl SliceList {
+    pub fn new(slices: Vec<FatSlice>, search: DebugSearch) -> Self {
+        Self {
+            ui_list: slices
+                .iter()
+                .map(|s| ListItem::new(format!("{:<12} {} bytes", s.arch, s.data.len())))
+                .collect(),
+            slices,
+            search,
+        }
+    }
+
+    pub fn load(&self, state: &ListState) -> Option<Box<dyn Binary>> {
+        let mut bin = binary::load(self.slices.get(state.selected()?)?.data)?;
+
+        bin.load_debug_file(&self.search);
+        Some(bin)
+    }
+}
+
+impl ScreenItem for SliceList {
+    fn title(&self) -> String {
+        "Architectures".to_owned()
+    }
+
+    fn draw(&self) -> List<'_> {
+        List::new(self.ui_list.clone())
+            .style(Style::default().fg(Color::White))
+            .highlight_style(Style::default().bg(Color::DarkGray))
+    }
+
+    fn list_size(&self) -> usize {
+        self.slices.len()
+    }
+
+    // Loading of slice changes the whole binary, so it is done by App
+    fn go_in(
+        &mut self,
+        _bin: &dyn Binary,
+        _dec: &'static Decoder,
+        _state: &mut ListState,
+    ) -> Option<ItemType> {
+        None
+    }
+}
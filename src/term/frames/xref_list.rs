@@ -0,0 +1,85 @@
+use super::{ItemType, ScreenItem};
+use crate::binary::{Binary, Function};
+use crate::decoder::Decoder;
+use crate::xref::Xref;
+use tui::{
+    style::{Color, Style},
+    widgets::{List, ListItem, ListState},
+};
+
+// Instructions referencing an address, picked one is opened by App
+pub struct XrefList {
+    name: String,
+    refs: Vec<Xref>,
+    ui_list: Vec<ListItem<'static>>,
+}
+
+impl XrefList {
+    pub fn new(name: String, refs: Vec<Xref>) -> Self {
+        let ui_list = if refs.is_empty() {
+            vec![ListItem::new("no references")]
+        } else {
+            refs.iter()
+                .map(|x| {
+                    ListItem::new(format!(
+                        "{:<6}0x{:<16x}{}+0x{:x}",
+                        x.kind.name(),
+                        x.from,
+                        x.func.name(),
+                        x.from - x.func.addr()
+                    ))
+                })
+                .collect()
+        };
+
+        Self {
+            name,
+            refs,
+            ui_list,
+        }
+    }
+
+    // Referencing instruction and function containing it
+    pub fn site(&self, state: &ListState) -> Option<(Function, u64)> {
+        let x = self.refs.get(state.selected()?)?;
+
+        Some((x.func.clone(), x.from))
+    }
+}
+
+impl ScreenItem for XrefList {
+    fn title(&self) -> String {
+        format!("References to {}", self.name)
+    }
+
+    fn draw(&self) -> List<'_> {
+        List::new(self.ui_list.clone())
+            .style(Style::default().fg(Color::White))
+            .highlight_style(Style::default().bg(Color::DarkGray))
+    }
+
+    fn list_size(&self) -> usize {
+        self.ui_list.len()
+    }
+
+    // Site is opened in a new listing with cursor on it, so it is done by App
+    fn go_in(
+        &mut self,
+        _bin: &dyn Binary,
+        _dec: &'static Decoder,
+        _state: &mut ListState,
+    ) -> Option<ItemType> {
+        None
+    }
+
+    fn find(&mut self, state: &mut ListState, s: &str) {
+        let start = state.selected().unwrap_or(0);
+
+        if let Some(i) = self.refs[start.min(self.refs.len())..]
+            .iter()
+            .position(|x| x.func.name().contains(s))
+        {
+            state.select(Some(start + i));
+        }
+    }
+}
@@ -37,7 +37,7 @@ impl Logger {
         self.log.push_back((msg, t));
     }
 
-    pub fn flush(&mut self) -> Paragraph {
+    pub fn flush(&mut self) -> Paragraph<'_> {
         let lines: Vec<_> = self
             .log
             .iter()
@@ -71,11 +71,14 @@ macro_rules! log_info {
             $crate::term::logger::LOGGER.push(format!("{}", format_args!($fmt, $($args)*)), $crate::term::logger::LogType::Info);
         }
     };
-    ($fmt:expr) => {
+    ($fmt:expr) => {{
+        let msg = ($fmt).to_string();
+
+        #[allow(static_mut_refs)]
         unsafe {
-            $crate::term::logger::LOGGER.push(($fmt).to_string(), $crate::term::logger::LogType::Info);
+            $crate::term::logger::LOGGER.push(msg, $crate::term::logger::LogType::Info);
         }
-    }
+    }}
 }
 
 #[macro_export]
@@ -85,11 +88,14 @@ macro_rules! log_debug {
             $crate::term::logger::LOGGER.push(format!("{}", format_args!($fmt, $($args)*)), $crate::term::logger::LogType::Debug);
         }
     };
-    ($fmt:expr) => {
+    ($fmt:expr) => {{
+        let msg = $fmt;
+
+        #[allow(static_mut_refs)]
         unsafe {
-            $crate::term::logger::LOGGER.push($fmt, $crate::term::logger::LogType::Debug);
+            $crate::term::logger::LOGGER.push(msg, $crate::term::logger::LogType::Debug);
         }
-    }
+    }}
 }
 
 #[macro_export]
@@ -99,9 +105,12 @@ macro_rules! log_warn {
             $crate::term::logger::LOGGER.push(format!($fmt, $(unsafe_render(&$arg)),*), $crate::term::logger::LogType::Warn);
         }
     };
-    ($fmt:expr) => {
+    ($fmt:expr) => {{
+        let msg = ($fmt).to_string();
+
+        #[allow(static_mut_refs)]
         unsafe {
-            $crate::term::logger::LOGGER.push(($fmt).to_string(), $crate::term::logger::LogType::Warn);
+            $crate::term::logger::LOGGER.push(msg, $crate::term::logger::LogType::Warn);
         }
-    }
+    }}
 }
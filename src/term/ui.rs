@@ -29,6 +29,13 @@ pub fn render(app: &mut App, f: &mut Frame) {
     let chunks_bottom = bottom_layout.split(*chunks.last().unwrap());
 
     let fr = app.active_main_frame();
+    let area = match fr.0.second_frame() {
+        Some(_) => upper_layout.split(chunks[0])[0],
+        None => chunks[0],
+    };
+
+    fr.0.resize(area);
+
     let second_frame = fr.0.second_frame();
     let list = fr.0.draw();
 
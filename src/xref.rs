@@ -0,0 +1,151 @@
+use crate::analysis::{self, BranchInst};
+use crate::binary::{Binary, Function};
+use crate::decoder::{Decoder, Inst};
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum XrefKind {
+    Call,
+    Jump,
+    Data,
+}
+
+impl XrefKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Call => "call",
+            Self::Jump => "jump",
+            Self::Data => "data",
+        }
+    }
+}
+
+// Instruction referencing an address and function it is in
+#[derive(Clone)]
+pub struct Xref {
+    pub from: u64,
+    pub func: Function,
+    pub kind: XrefKind,
+}
+
+// Every address code of the binary refers to, built once at load
+pub struct Xrefs {
+    refs: HashMap<u64, Vec<Xref>>,
+}
+
+impl Xrefs {
+    pub fn new(bin: &dyn Binary, dec: &Decoder) -> Self {
+        let mut s = Self {
+            refs: HashMap::new(),
+        };
+
+        for f in bin.function_names() {
+            s.add_function(bin, dec, &f);
+        }
+
+        s
+    }
+
+    pub fn add_function(&mut self, bin: &dyn Binary, dec: &Decoder, f: &Function) {
+        for inst in dec.decode(bin, f) {
+            for (to, kind) in Self::targets(bin, &inst) {
+                self.refs.entry(to).or_default().push(Xref {
+                    from: inst.addr,
+                    func: f.clone(),
+                    kind,
+                });
+            }
+        }
+    }
+
+    // References to addr, in order of address of referencing instruction
+    pub fn to(&self, addr: u64) -> Vec<Xref> {
+        let mut v = self.refs.get(&addr).cloned().unwrap_or_default();
+
+        v.sort_by_key(|x| x.from);
+        v
+    }
+
+    // Functions calling the one at addr
+    pub fn callers(&self, addr: u64) -> Vec<Function> {
+        let mut v: Vec<Function> = Vec::new();
+
+        for x in self.to(addr) {
+            if x.kind == XrefKind::Call && !v.iter().any(|f| f.addr() == x.func.addr()) {
+                v.push(x.func);
+            }
+        }
+
+        v
+    }
+
+    fn targets(bin: &dyn Binary, inst: &Inst) -> Vec<(u64, XrefKind)> {
+        let kind = match inst.branch {
+            Some(BranchInst::Call(_)) | Some(BranchInst::CallVia(_)) => XrefKind::Call,
+            Some(BranchInst::Jump(_)) | Some(BranchInst::JumpVia(_)) => XrefKind::Jump,
+            _ => XrefKind::Data,
+        };
+
+        if let Some(r) = analysis::reloc(bin, inst) {
+            return r.target.map(|t| (t, kind)).into_iter().collect();
+        }
+
+        let mut v = Vec::new();
+
+        match inst.branch {
+            Some(BranchInst::Call(x)) | Some(BranchInst::Jump(x)) => v.push((x, kind)),
+            // Calls through GOT count as calls of the stub, so callers of
+            // import are all in one place
+            Some(BranchInst::CallVia(slot)) | Some(BranchInst::JumpVia(slot)) => {
+                match bin.import_by_got(slot).and_then(|i| i.stub) {
+                    // Stub itself jumping through the slot
+                    Some(s) if s.addr() <= inst.addr && inst.addr < s.addr() + s.size() as u64 => {}
+                    Some(s) => v.push((s.addr(), kind)),
+                    None => v.push((slot, kind)),
+                }
+            }
+            _ => {}
+        }
+
+        if let Some(t) = inst.target {
+            if !v.iter().any(|(a, _)| *a == t) {
+                v.push((t, XrefKind::Data));
+            }
+        }
+
+        // Many cases usually share the default target
+        for c in &inst.cases {
+            if !v.iter().any(|(a, _)| a == c) {
+                v.push((*c, XrefKind::Jump));
+            }
+        }
+
+        v
+    }
+}
+
+// Functions called from f, in order of first call, resolved as listing does
+pub fn callees(bin: &dyn Binary, dec: &Decoder, f: &Function) -> Vec<Function> {
+    let mut v: Vec<Function> = Vec::new();
+
+    for inst in dec.decode(bin, f) {
+        let target = match analysis::reloc(bin, &inst) {
+            Some(r) => match inst.branch {
+                Some(BranchInst::Call(_)) => r.target,
+                _ => None,
+            },
+            None => match inst.branch {
+                Some(BranchInst::Call(x)) => Some(x),
+                _ => None,
+            },
+        };
+
+        if let Some(c) = target.and_then(|t| bin.function_by_addr(t)) {
+            if !v.iter().any(|x| x.addr() == c.addr()) {
+                v.push(c);
+            }
+        }
+    }
+
+    v
+}